@@ -6,6 +6,8 @@ pub struct Bytecodes {
     pub code_capacity: usize,
 
     pub code: Vec<u8>,
+    // source line of each byte of `code`
+    pub lines: Vec<i64>,
 
     pub values_count: usize,
     pub values_capacity: usize,
@@ -19,21 +21,28 @@ impl Bytecodes {
             code_count: 0,
             code_capacity: 0,
             code: Vec::new(),
+            lines: Vec::new(),
             values_count: 0,
             values_capacity: 0,
             values: Vec::new(),
         }
     }
 
-    pub fn write(&mut self, byte: u8) -> usize {
+    pub fn write(&mut self, byte: u8, line: i64) -> usize {
         self.code.push(byte);
+        self.lines.push(line);
         self.code_count += 1;
         &self.code_count - 1
     }
 
-    pub fn write2(&mut self, byte1: u8, byte2: u8) -> usize {
-        self.write(byte1);
-        self.write(byte2)
+    pub fn write2(&mut self, byte1: u8, byte2: u8, line: i64) -> usize {
+        self.write(byte1, line);
+        self.write(byte2, line)
+    }
+
+    /// Source line the byte at `offset` was compiled from, 0 when unknown.
+    pub fn line_at(&self, offset: usize) -> i64 {
+        self.lines.get(offset).copied().unwrap_or(0)
     }
 
     pub fn add_const_val(&mut self, val: f64) -> usize {
@@ -47,8 +56,9 @@ impl Bytecodes {
     }
 
     pub fn free(&mut self) {
-        self.code.resize(0, 0);
-        self.values.resize(0, Value::new());
+        self.code.clear();
+        self.lines.clear();
+        self.values.clear();
         self.code_count = 0;
         self.code_capacity = 0;
         self.values_capacity = 0;
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::compiler::ClassContext;
+use crate::function::FunctionType;
+use crate::object::{Enum, VariantDecl};
+use crate::opcode::OpCode::*;
+use crate::parser::Parser;
+use crate::token::TokenType::*;
+use crate::value::{Value, ValueRepr};
+
+impl Parser {
+    /// Compiles `class Name [< Superclass] [with Trait, ...] { members }`.
+    pub fn class_declaration(&mut self) {
+        self.consume(&TokenIdentifier, "Expect class name.");
+        let class_name = self.prev_tok.clone().unwrap();
+        let name_constant = self.ident_const();
+        self.declare_var();
+
+        self.codegen.emit_op_operand(OpClass, name_constant as u8);
+        self.define_var(name_constant as u8);
+
+        self.classes.push(ClassContext::default());
+
+        if self.match_advance(&TokenLess) {
+            self.consume(&TokenIdentifier, "Expect superclass name.");
+            let superclass = self.prev_tok.clone().unwrap();
+            if superclass.raw == class_name.raw {
+                self.error("A class can't inherit from itself.");
+            }
+            self.load_variable(&superclass.raw);
+
+            // `super` lives in its own scope so every method closes over the same superclass
+            self.scope.begin_scope();
+            self.scope.add_local(&"super".to_string());
+
+            self.load_variable(&class_name.raw);
+            self.codegen.emit_op(OpInherit);
+            self.classes.last_mut().unwrap().has_superclass = true;
+        }
+
+        if self.curr_tok.as_ref().is_some_and(|tok| tok.is(TokenIdentifier) && tok.raw == "with") {
+            self.advance();
+            self.mixins(&class_name.raw);
+        }
+
+        self.load_variable(&class_name.raw);
+        self.consume(&TokenLeftBrace, "Expect '{' before class body.");
+        while !self.curr_is(&TokenRightBrace) && !self.curr_is(&TokenEof) {
+            self.member();
+        }
+        self.consume(&TokenRightBrace, "Expect '}' after class body.");
+        self.codegen.emit_op(OpPop);
+
+        if self.classes.pop().unwrap().has_superclass {
+            self.codegen.emit_op_operand(OpPopN, self.scope.end_scope());
+        }
+    }
+
+    /// Compiles `trait Name { members }`, its members are declared as in a class and methods may
+    /// start with `fun`.
+    pub fn trait_declaration(&mut self) {
+        self.consume(&TokenIdentifier, "Expect trait name.");
+        let trait_name = self.prev_tok.clone().unwrap();
+        let name_constant = self.ident_const();
+        self.declare_var();
+
+        self.codegen.emit_op_operand(OpTrait, name_constant as u8);
+        self.define_var(name_constant as u8);
+
+        self.classes.push(ClassContext::default());
+        self.load_variable(&trait_name.raw);
+        self.consume(&TokenLeftBrace, "Expect '{' before trait body.");
+        while !self.curr_is(&TokenRightBrace) && !self.curr_is(&TokenEof) {
+            self.match_advance(&TokenFun);
+            self.member();
+        }
+        self.consume(&TokenRightBrace, "Expect '}' after trait body.");
+        self.codegen.emit_op(OpPop);
+
+        let members = self.classes.pop().unwrap().members;
+        if self.scope.scope_depth == 0 {
+            self.traits.insert(trait_name.raw, members);
+        }
+    }
+
+    /// Compiles the traits listed after `with`, mixing them into the class in order. Two global traits
+    /// declared in this file defining the same member is a compile error, `OpMixin` catches the rest.
+    fn mixins(&mut self, class_name: &str) {
+        let mut defined_by: HashMap<String, String> = HashMap::new();
+        loop {
+            self.consume(&TokenIdentifier, "Expect trait name.");
+            let trait_name = self.prev_tok.clone().unwrap().raw;
+            let members = match self.resolve_variable(&trait_name).0 {
+                OpGetGlobal => self.traits.get(&trait_name).cloned().unwrap_or_default(),
+                _ => Vec::new(),
+            };
+            for member in members {
+                match defined_by.get(&member) {
+                    Some(other) if *other != trait_name => {
+                        let msg =
+                            format!("Method '{}' is defined in both traits {} and {}.", member, other, trait_name);
+                        self.error(&msg);
+                    }
+                    _ => {
+                        defined_by.insert(member, trait_name.clone());
+                    }
+                }
+            }
+
+            self.load_variable(&trait_name);
+            self.load_variable(class_name);
+            self.codegen.emit_op(OpMixin);
+            if !self.match_advance(&TokenComma) {
+                break;
+            }
+        }
+    }
+
+    /// Compiles `enum Name { Variant, Variant(field, ...), ... }`. The enum is known entirely at
+    /// compile time, so it is stored as a constant.
+    pub fn enum_declaration(&mut self) {
+        self.consume(&TokenIdentifier, "Expect enum name.");
+        let enum_name = self.prev_tok.clone().unwrap();
+        let name_constant = self.ident_const();
+        self.declare_var();
+
+        self.consume(&TokenLeftBrace, "Expect '{' before enum body.");
+        let mut variants: Vec<VariantDecl> = Vec::new();
+        while !self.curr_is(&TokenRightBrace) && !self.curr_is(&TokenEof) {
+            self.consume(&TokenIdentifier, "Expect variant name.");
+            let name = self.prev_tok.clone().unwrap().raw;
+            if variants.iter().any(|variant| variant.name == name) {
+                self.error(&format!("Duplicate variant '{}'.", name));
+            }
+
+            let mut fields = Vec::new();
+            if self.match_advance(&TokenLeftParen) {
+                while !self.curr_is(&TokenRightParen) && !self.curr_is(&TokenEof) {
+                    self.consume(&TokenIdentifier, "Expect field name.");
+                    fields.push(self.prev_tok.clone().unwrap().raw);
+                    if !self.match_advance(&TokenComma) {
+                        break;
+                    }
+                }
+                self.consume(&TokenRightParen, "Expect ')' after variant fields.");
+            }
+            variants.push(VariantDecl { name, fields });
+
+            if !self.match_advance(&TokenComma) {
+                break;
+            }
+        }
+        self.consume(&TokenRightBrace, "Expect '}' after enum body.");
+
+        let enum_ = Enum { name: enum_name.raw, variants };
+        let constant = self.codegen.bytecodes.add_const(Value(ValueRepr::Enum(Rc::new(enum_))));
+        self.codegen.emit_op_operand(OpConstant, constant as u8);
+        self.define_var(name_constant as u8);
+    }
+
+    /// Compiles a member of the class body, the class is on top of the stack:
+    /// - `name(params) { body }`, a method
+    /// - `get name() { body }` and `set name(value) { body }`, accessors run on `object.name`
+    /// - `name = value;`, a field every instance starts with, evaluated before `init` runs
+    /// - `static name(params) { body }` and `static name = value;`, members of the class object itself
+    ///
+    /// `get`, `set` and `static` are only modifiers when another name follows them.
+    fn member(&mut self) {
+        self.consume(&TokenIdentifier, "Expect member name.");
+        if self.is_modifier("static") {
+            self.advance();
+            return self.static_member();
+        }
+
+        let accessor = match self.is_modifier("get") || self.is_modifier("set") {
+            true => Some(self.prev_tok.clone().unwrap().raw),
+            false => None,
+        };
+        if accessor.is_some() {
+            self.advance();
+        }
+        let name = self.prev_tok.clone().unwrap();
+        let constant = self.ident_const();
+        self.classes.last_mut().unwrap().members.push(name.raw.clone());
+
+        let (function_type, op) = match accessor.as_deref() {
+            Some("get") => (FunctionType::TypeGetter, OpGetter),
+            Some(_) => (FunctionType::TypeSetter, OpSetter),
+            None if self.curr_is(&TokenEqual) || self.curr_is(&TokenSemicolon) => {
+                return self.field(&name.raw, constant);
+            }
+            None if name.raw == "init" => (FunctionType::TypeInitializer, OpMethod),
+            None => (FunctionType::TypeMethod, OpMethod),
+        };
+        self.function(function_type, &name.raw);
+        self.codegen.emit_op_operand(op, constant as u8);
+    }
+
+    /// Whether the name just consumed is the modifier `word`, which needs a member name after it.
+    fn is_modifier(&self, word: &str) -> bool {
+        self.prev_tok.as_ref().is_some_and(|tok| tok.raw == word) && self.curr_is(&TokenIdentifier)
+    }
+
+    /// Compiles the initializer of field `name` as a method of no parameters returning its value.
+    fn field(&mut self, name: &str, constant: usize) {
+        self.begin_function(FunctionType::TypeMethod);
+        self.scope.begin_scope();
+        if self.match_advance(&TokenEqual) {
+            self.expression();
+        } else {
+            self.codegen.emit_op(OpNil);
+        }
+        self.codegen.emit_return();
+        self.consume(&TokenSemicolon, "Expect ';' after field initializer.");
+
+        self.end_function(name, Vec::new());
+        self.codegen.emit_op_operand(OpField, constant as u8);
+    }
+
+    /// Compiles a static method, or a static field evaluated once when the class is defined.
+    fn static_member(&mut self) {
+        let name = self.prev_tok.clone().unwrap();
+        let constant = self.ident_const();
+        self.classes.last_mut().unwrap().members.push(name.raw.clone());
+
+        self.classes.last_mut().unwrap().in_static = true;
+        if self.curr_is(&TokenLeftParen) {
+            self.function(FunctionType::TypeFunction, &name.raw);
+        } else {
+            if self.match_advance(&TokenEqual) {
+                self.expression();
+            } else {
+                self.codegen.emit_op(OpNil);
+            }
+            self.consume(&TokenSemicolon, "Expect ';' after static field.");
+        }
+        self.classes.last_mut().unwrap().in_static = false;
+
+        self.codegen.emit_op_operand(OpStatic, constant as u8);
+    }
+
+    /// Compiles `object.name` and `object.name = value`.
+    pub fn dot(&mut self, can_assign: bool) {
+        self.consume(&TokenIdentifier, "Expect property name after '.'.");
+        let name = self.ident_const();
+
+        if can_assign && self.match_advance(&TokenEqual) {
+            self.expression();
+            self.codegen.emit_op_operand(OpSetProperty, name as u8);
+        } else if let Some(op) = self.compound_assignment(can_assign) {
+            self.codegen.emit_op(OpDup);
+            self.codegen.emit_op_operand(OpGetProperty, name as u8);
+            self.expression();
+            self.codegen.emit_op(op);
+            self.codegen.emit_op_operand(OpSetProperty, name as u8);
+        } else {
+            self.codegen.emit_op_operand(OpGetProperty, name as u8);
+        }
+    }
+
+    pub fn this_(&mut self, _can_assign: bool) {
+        match self.classes.last() {
+            None => return self.error("Can't use 'this' outside of a class."),
+            Some(class) if class.in_static => return self.error("Can't use 'this' in a static member."),
+            Some(_) => {}
+        }
+
+        self.load_variable("this");
+    }
+
+    /// Compiles `super.name`, the superclass method bound to `this`.
+    pub fn super_(&mut self, _can_assign: bool) {
+        match self.classes.last() {
+            None => self.error("Can't use 'super' outside of a class."),
+            Some(class) if !class.has_superclass => self.error("Can't use 'super' in a class with no superclass."),
+            Some(_) => {}
+        }
+
+        self.consume(&TokenDot, "Expect '.' after 'super'.");
+        self.consume(&TokenIdentifier, "Expect superclass method name.");
+        let name = self.ident_const();
+
+        self.load_variable("this");
+        self.load_variable("super");
+        self.codegen.emit_op_operand(OpGetSuper, name as u8);
+    }
+
+    pub(crate) fn load_variable(&mut self, name: &str) {
+        let (get_op, _, arg) = self.resolve_variable(name);
+        self.codegen.emit_op_operand(get_op, arg);
+    }
+}
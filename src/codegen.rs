@@ -1,25 +1,54 @@
 use crate::bytecodes::Bytecodes;
 use crate::opcode::OpCode;
-use crate::opcode::OpCode::{OpConstant, OpReturn};
+use crate::opcode::OpCode::{OpCall, OpConstant, OpReturn, OpTailCall};
 use crate::value::{Value, ValueRepr};
 
 pub struct Codegen {
     pub bytecodes: Bytecodes,
+    // offset of the last `OpCall` emitted
+    last_call: Option<usize>,
+    // source line the code emitted next is attributed to
+    pub line: i64,
+    // values on the function's stack once the code emitted so far ran, counted along straight-line
+    // code; the parser sets it back to the number of locals at every statement
+    pub stack_depth: usize,
+}
+
+impl Default for Codegen {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Codegen {
     pub fn new() -> Self {
-        Codegen { bytecodes: Bytecodes::new() }
+        Codegen { bytecodes: Bytecodes::new(), last_call: None, line: 0, stack_depth: 0 }
+    }
+
+    pub fn emit_call(&mut self, argc: u8) -> usize {
+        let operand = self.emit_op_operand(OpCall, argc);
+        self.last_call = Some(operand - 1);
+        operand
+    }
+
+    /// Turns the code's last instruction into `OpTailCall` if it is an `OpCall`, so the return
+    /// following it reuses the frame instead.
+    pub fn make_tail_call(&mut self) {
+        if self.last_call.is_some_and(|at| at + 2 == self.bytecodes.code.len()) {
+            self.bytecodes.code[self.last_call.unwrap()] = OpTailCall.into();
+        }
     }
 
     pub fn emit_op_operand2(&mut self, op: OpCode, o1: u8, o2: u8) -> usize {
-        self.emit_op(op);
+        self.track(op, o1);
+        self.emit_byte(op.into());
         self.emit_byte(o1);
         self.emit_byte(o2)
     }
 
     pub fn emit_op_operand(&mut self, op: OpCode, o: u8) -> usize {
-        self.emit_op(op);
+        self.track(op, o);
+        self.emit_byte(op.into());
         self.emit_byte(o)
     }
 
@@ -29,29 +58,42 @@ impl Codegen {
     }
 
     pub fn emit_op(&mut self, op: OpCode) -> usize {
+        self.track(op, 0);
         self.emit_byte(op.into())
     }
 
+    fn track(&mut self, op: OpCode, operand: u8) {
+        self.stack_depth = (self.stack_depth as i32 + op.stack_effect(operand)).max(0) as usize;
+    }
+
     pub fn emit_byte(&mut self, b: u8) -> usize {
-        self.bytecodes.write(b)
+        self.bytecodes.write(b, self.line)
     }
 
     pub fn emit_bytes(&mut self, bytes: &[u8]) -> usize {
         let mut size: usize = 0;
         for b in bytes {
-            size = self.bytecodes.write(*b)
+            size = self.bytecodes.write(*b, self.line)
         }
         size
     }
 
     pub fn emit_return(&mut self) -> usize {
-        self.bytecodes.write(OpReturn.into())
+        self.emit_op(OpReturn)
     }
 
     pub fn emit_const_f64(&mut self, value: f64) -> usize {
         let addr = self.bytecodes.add_const_val(value);
         // TODO: add max constant check
-        self.bytecodes.write2(OpConstant.into(), addr as u8);
+        self.emit_op_operand(OpConstant, addr as u8);
+
+        addr
+    }
+
+    pub fn emit_const_i64(&mut self, value: i64) -> usize {
+        let addr = self.bytecodes.add_const(Value(ValueRepr::Integer(value)));
+        // TODO: add max constant check
+        self.emit_op_operand(OpConstant, addr as u8);
 
         addr
     }
@@ -59,7 +101,7 @@ impl Codegen {
     pub fn emit_const_string(&mut self, str: String) -> usize {
         let addr = self.bytecodes.add_const(Value(ValueRepr::String(str)));
         // TODO: add max constant check
-        self.bytecodes.write2(OpConstant.into(), addr as u8);
+        self.emit_op_operand(OpConstant, addr as u8);
 
         addr
     }
@@ -70,21 +112,29 @@ impl Codegen {
         self.bytecodes.code_count - 2
     }
 
+    /// Emits `OpTry` whose handler unwinds to `keep` values below the current stack top, returning
+    /// the catch and finally jumps to patch. An unpatched finally jump means there is none.
+    pub fn emit_try(&mut self, keep: u8) -> (usize, usize) {
+        self.emit_op_operand(OpCode::OpTry, keep);
+        self.emit_bytes(&[0xFF, 0xFF, 0x00, 0x00]);
+        (self.bytecodes.code_count - 4, self.bytecodes.code_count - 2)
+    }
+
     pub fn patch_jump(&mut self, offset: usize) {
         let jump = self.bytecodes.code_count - offset - 2;
-        if (jump as u16) > u16::MAX {
+        if jump > u16::MAX as usize {
             eprintln!("Too much code to jump over.");
         }
 
-        self.bytecodes.code[offset as usize] = ((jump >> 8) & 0xFF) as u8;
-        self.bytecodes.code[(offset + 1) as usize] = (jump & 0xFF) as u8;
+        self.bytecodes.code[offset] = ((jump >> 8) & 0xFF) as u8;
+        self.bytecodes.code[offset + 1] = (jump & 0xFF) as u8;
     }
 
     pub fn emit_loop(&mut self, start: usize) {
         self.emit_op(OpCode::OpLoop);
 
-        let offset = (self.bytecodes.code_count - start + 2) as u16;
-        if offset > u16::MAX {
+        let offset = self.bytecodes.code_count - start + 2;
+        if offset > u16::MAX as usize {
             eprintln!("Loop body too large.");
         }
 
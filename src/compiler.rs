@@ -1,33 +1,69 @@
 use crate::bytecodes::Bytecodes;
 use crate::codegen::Codegen;
 use crate::debug;
+use crate::function::FunctionType;
 use crate::lexer::Lexer;
 use crate::localscope::LocalScope;
 use crate::parser::Parser;
 use crate::token::TokenType::TokenEof;
-use crate::value::ValueRepr;
 
+/// State of a function being compiled, the parser keeps one per enclosing function while it
+/// compiles a nested function body.
 pub struct Compiler {
-    pub function: ValueRepr,
+    pub function_type: FunctionType,
     pub scope: LocalScope,
     pub codegen: Codegen,
+    pub loops: Vec<LoopContext>,
+    pub tries: usize,
+}
+
+/// Loop being compiled, `break` jumps are collected and patched once the loop's end is known.
+pub struct LoopContext {
+    pub label: Option<String>,
+    // target of `continue`
+    pub start: usize,
+    // scope depth outside the loop body, locals deeper than this are popped when leaving early
+    pub depth: i8,
+    // try blocks open outside the loop, the ones opened inside are dropped when leaving early
+    pub tries: usize,
+    pub breaks: Vec<usize>,
+    // slot of a `for-in` loop's iterator, closed when the loop is left early
+    pub iterator: Option<u8>,
+}
+
+/// Class or trait whose body is being compiled, tells `this` and `super` whether they are valid.
+#[derive(Default)]
+pub struct ClassContext {
+    pub has_superclass: bool,
+    // compiling a static member, which has no `this`
+    pub in_static: bool,
+    // names of the members declared so far
+    pub members: Vec<String>,
 }
 
 pub struct CompilerScope {
 
 }
 
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Compiler {
     pub fn new() -> Self {
         Self {
-            function: Default::default(),
+            function_type: FunctionType::TypeScript,
             scope: LocalScope::new(),
             codegen: Codegen::new(),
+            loops: Vec::new(),
+            tries: 0,
         }
     }
 }
 
-pub fn compile(input: String) -> Bytecodes {
+pub fn compile(input: String) -> Option<Bytecodes> {
     let mut parser = Parser::new(Lexer::new(input));
     parser.advance();
 
@@ -41,7 +77,8 @@ pub fn compile(input: String) -> Bytecodes {
 
     if parser.had_error {
         debug::debug_bytecode(&parser.codegen.bytecodes, "MAIN");
+        return None;
     }
 
-    parser.codegen.bytecodes.to_owned()
+    Some(parser.codegen.bytecodes.to_owned())
 }
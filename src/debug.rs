@@ -1,8 +1,9 @@
 use crate::bytecodes::Bytecodes;
 use crate::opcode::OpCode;
+use crate::value::ValueRepr;
 
 pub fn debug_bytecode(bytecodes: &Bytecodes, name: &str) {
-    print!("=={}==\n", name);
+    println!("=={}==", name);
 
     let mut offset = 0usize;
 
@@ -11,7 +12,17 @@ pub fn debug_bytecode(bytecodes: &Bytecodes, name: &str) {
             break;
         }
 
-        offset = debug_instruction(&bytecodes, offset);
+        offset = debug_instruction(bytecodes, offset);
+    }
+
+    // then the functions declared in this code, with their parameters' default values
+    for value in bytecodes.values.iter() {
+        if let ValueRepr::Function(function) = &value.0 {
+            debug_bytecode(&function.bytecodes, &function.name);
+            for default in function.params.iter().filter_map(|param| param.default.as_ref()) {
+                debug_bytecode(&default.function.bytecodes, &format!("{} default", default.function.name));
+            }
+        }
     }
 }
 
@@ -24,7 +35,7 @@ fn debug_instruction(bytecodes: &Bytecodes, offset: usize) -> usize {
             offset + 1
         }
         Some(op) => {
-            let op: OpCode = op.clone().into();
+            let op: OpCode = (*op).into();
             match op {
                 OpCode::OpReturn => simple_instruction(&op, offset),
                 OpCode::OpConstant => constant_instruction(&op, bytecodes, offset),
@@ -33,6 +44,15 @@ fn debug_instruction(bytecodes: &Bytecodes, offset: usize) -> usize {
                 OpCode::OpSubtract => simple_instruction(&op, offset),
                 OpCode::OpMultiple => simple_instruction(&op, offset),
                 OpCode::OpDivide => simple_instruction(&op, offset),
+                OpCode::OpFloorDivide => simple_instruction(&op, offset),
+                OpCode::OpModulo => simple_instruction(&op, offset),
+                OpCode::OpPower => simple_instruction(&op, offset),
+                OpCode::OpBitAnd => simple_instruction(&op, offset),
+                OpCode::OpBitOr => simple_instruction(&op, offset),
+                OpCode::OpBitXor => simple_instruction(&op, offset),
+                OpCode::OpBitNot => simple_instruction(&op, offset),
+                OpCode::OpShiftLeft => simple_instruction(&op, offset),
+                OpCode::OpShiftRight => simple_instruction(&op, offset),
                 OpCode::OpNil => simple_instruction(&op, offset),
                 OpCode::OpTrue => simple_instruction(&op, offset),
                 OpCode::OpFalse => simple_instruction(&op, offset),
@@ -51,6 +71,50 @@ fn debug_instruction(bytecodes: &Bytecodes, offset: usize) -> usize {
                 OpCode::OpJumpIfFalse => jump_instruction(&op, 1, bytecodes, offset),
                 OpCode::OpJump => jump_instruction(&op, 1, bytecodes, offset),
                 OpCode::OpLoop => jump_instruction(&op, -1, bytecodes, offset),
+                OpCode::OpCall => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpBuildList => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpBuildMap => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpIndexGet => simple_instruction(&op, offset),
+                OpCode::OpIndexSet => simple_instruction(&op, offset),
+                OpCode::OpRange => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpIterInit => simple_instruction(&op, offset),
+                OpCode::OpIterNext => jump_instruction(&op, 1, bytecodes, offset),
+                OpCode::OpGetUpvalue => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpSetUpvalue => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpClosure => closure_instruction(&op, bytecodes, offset),
+                OpCode::OpBuildTuple => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpUnpack => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpDup => simple_instruction(&op, offset),
+                OpCode::OpInRange => simple_instruction(&op, offset),
+                OpCode::OpJumpIfNil => jump_instruction(&op, 1, bytecodes, offset),
+                OpCode::OpClass => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpGetProperty => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpSetProperty => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpMethod => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpInherit => simple_instruction(&op, offset),
+                OpCode::OpGetSuper => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpDup2 => simple_instruction(&op, offset),
+                OpCode::OpTry => try_instruction(&op, bytecodes, offset),
+                OpCode::OpPopTry => simple_instruction(&op, offset),
+                OpCode::OpThrow => simple_instruction(&op, offset),
+                OpCode::OpEndFinally => simple_instruction(&op, offset),
+                OpCode::OpYield => simple_instruction(&op, offset),
+                OpCode::OpAwait => simple_instruction(&op, offset),
+                OpCode::OpCallNamed => call_named_instruction(&op, bytecodes, offset),
+                OpCode::OpSpread => simple_instruction(&op, offset),
+                OpCode::OpCallSpread => simple_instruction(&op, offset),
+                OpCode::OpTailCall => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpGetter => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpSetter => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpField => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpStatic => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpTrait => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpMixin => simple_instruction(&op, offset),
+                OpCode::OpMatchVariant => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpImport => constant_instruction(&op, bytecodes, offset),
+                OpCode::OpUnpackReversed => byte_instruction(&op, bytecodes, offset),
+                OpCode::OpLeave => leave_instruction(&op, bytecodes, offset),
+                OpCode::OpIterClose => simple_instruction(&op, offset),
                 OpCode::OpUnKnown => {
                     println!("Unknown opcode {:?}", op);
                     offset + 1
@@ -61,13 +125,13 @@ fn debug_instruction(bytecodes: &Bytecodes, offset: usize) -> usize {
 }
 
 fn simple_instruction(op: &OpCode, offset: usize) -> usize {
-    print!("{}\n", op);
+    println!("{}", op);
     offset + 1
 }
 
 fn jump_instruction(op: &OpCode, sign: i16, bytecodes: &Bytecodes, offset: usize) -> usize {
-    let jump: u16 = (bytecodes.code.get(offset + 1).unwrap().clone() as u16) << 8;
-    let jump = jump | bytecodes.code.get(offset + 2).unwrap().clone() as u16;
+    let jump: u16 = (*bytecodes.code.get(offset + 1).unwrap() as u16) << 8;
+    let jump = jump | *bytecodes.code.get(offset + 2).unwrap() as u16;
 
     let str_pad = calc_str_op_padding(op);
 
@@ -76,6 +140,21 @@ fn jump_instruction(op: &OpCode, sign: i16, bytecodes: &Bytecodes, offset: usize
     offset + 3
 }
 
+fn try_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> usize {
+    let read_short = |at: usize| (bytecodes.code[at] as usize) << 8 | bytecodes.code[at + 1] as usize;
+    let keep = bytecodes.code[offset + 1];
+    let catch = offset + 4 + read_short(offset + 2);
+    let finally = match read_short(offset + 4) {
+        0 => "none".to_string(),
+        jump => (offset + 6 + jump).to_string(),
+    };
+
+    let str_pad = calc_str_op_padding(op);
+    println!("{:->width$} {} {} catch -> {} finally -> {}", op, keep, offset, catch, finally, width = str_pad);
+
+    offset + 6
+}
+
 fn byte_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> usize {
     let slot = bytecodes.code.get(offset + 1).unwrap();
 
@@ -85,10 +164,33 @@ fn byte_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> usize
     offset + 2
 }
 
+fn leave_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> usize {
+    let pops = bytecodes.code[offset + 1];
+    let handlers = bytecodes.code[offset + 2];
+
+    let str_pad = calc_str_op_padding(op);
+    println!("{:->width$} {} {}", op, pops, handlers, width = str_pad);
+
+    offset + 3
+}
+
+fn call_named_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> usize {
+    let argc = bytecodes.code[offset + 1];
+    let names = bytecodes.code[offset + 2];
+
+    let str_pad = calc_str_op_padding(op);
+    print!("{:->width$} {} {} ", op, argc, names, width = str_pad);
+    match bytecodes.values.get(names as usize) {
+        Some(val) => val.print(),
+        None => println!("Unknown constant value {}", names),
+    }
+
+    offset + 3
+}
+
 fn calc_str_op_padding(op: &OpCode) -> usize {
     let str_len = op.to_string().len();
-    let str_pad = if str_len > 16 { 0 } else { 16 - str_len };
-    str_pad
+    16usize.saturating_sub(str_len)
 }
 
 fn constant_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> usize {
@@ -96,7 +198,7 @@ fn constant_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> us
         Some(constant) => {
             let str_pad = calc_str_op_padding(op);
             print!("{:->width$} {} ", op, constant, width = str_pad);
-            constant.clone()
+            *constant
         }
         None => {
             print!("unknown value on index {}", offset);
@@ -111,3 +213,21 @@ fn constant_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> us
 
     offset + 2
 }
+
+fn closure_instruction(op: &OpCode, bytecodes: &Bytecodes, offset: usize) -> usize {
+    let next = constant_instruction(op, bytecodes, offset);
+    let constant = bytecodes.code[offset + 1];
+
+    let upvalue_count = match bytecodes.values.get(constant as usize).map(|v| &v.0) {
+        Some(ValueRepr::Function(function)) => function.upvalue_count as usize,
+        _ => 0,
+    };
+
+    for i in 0..upvalue_count {
+        let is_local = bytecodes.code[next + i * 2];
+        let index = bytecodes.code[next + i * 2 + 1];
+        println!("{offset:0>width$} |{:>19} {}", if is_local == 1 { "local" } else { "upvalue" }, index, width = 5);
+    }
+
+    next + upvalue_count * 2
+}
@@ -0,0 +1,131 @@
+use std::path::{Component, Path, PathBuf};
+
+/// What the file natives of a VM may touch, the embedding application sets it with
+/// [`VM::set_file_access`](crate::vm::VM::set_file_access). Paths scripts pass are relative to
+/// the root directory and can't leave it, through `..` or symbolic links alike.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FileAccess {
+    #[default]
+    None,
+    ReadOnly(PathBuf),
+    ReadWrite(PathBuf),
+}
+
+impl FileAccess {
+    /// Access to the files under `root`, which may also be written when `write` is set.
+    pub fn under(root: PathBuf, write: bool) -> FileAccess {
+        match write {
+            true => FileAccess::ReadWrite(root),
+            false => FileAccess::ReadOnly(root),
+        }
+    }
+
+    /// Finds the file a script means by `path`, when it may read it or also write it. A path to be
+    /// written never resolves to the root itself, `remove(".")` must not delete it.
+    pub fn resolve(&self, path: &str, write: bool) -> Result<PathBuf, String> {
+        let root = match self {
+            FileAccess::None => return Err("File access is not allowed.".to_string()),
+            FileAccess::ReadOnly(_) if write => return Err("File access is read-only.".to_string()),
+            FileAccess::ReadOnly(root) | FileAccess::ReadWrite(root) => root,
+        };
+        let root = root.canonicalize().map_err(|e| format!("Could not open '{}': {}.", root.display(), e))?;
+
+        match canonicalize_existing(&normalize(&root.join(path))) {
+            Some(file) if write && file == root => Err(format!("Path '{}' is the allowed directory itself.", path)),
+            Some(file) if file.starts_with(&root) => Ok(file),
+            _ => Err(format!("Path '{}' is outside the allowed directory.", path)),
+        }
+    }
+}
+
+/// Drops the `.` and `..` components of an absolute path.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
+    }
+    normal
+}
+
+/// Resolves the symbolic links of the part of `path` that exists, the file may not exist yet.
+/// `None` for a symbolic link whose target is missing, writing through it would create the
+/// target wherever it points.
+fn canonicalize_existing(path: &Path) -> Option<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return Some(missing.iter().rev().fold(canonical, |path, name| path.join(name)));
+        }
+        if existing.symlink_metadata().is_ok() {
+            return None;
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return Some(path.to_path_buf()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    /// Fresh empty directory under the system temp directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("apoloo-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn resolves_missing_files_inside_the_root() {
+        let root = temp_dir("missing");
+        let access = FileAccess::ReadWrite(root.clone());
+        let file = access.resolve("sub/../new.txt", true).unwrap();
+        assert_eq!(file, root.canonicalize().unwrap().join("new.txt"));
+        assert!(access.resolve("../outside.txt", true).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn root_can_be_read_but_not_written() {
+        let root = temp_dir("root");
+        let access = FileAccess::ReadWrite(root.clone());
+        for path in ["", ".", "sub/..", "./"] {
+            assert_eq!(access.resolve(path, false).unwrap(), root.canonicalize().unwrap());
+            let error = access.resolve(path, true).unwrap_err();
+            assert_eq!(error, format!("Path '{}' is the allowed directory itself.", path));
+        }
+        assert!(access.resolve("sub", true).is_ok());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_dangling_symlinks() {
+        let root = temp_dir("dangling");
+        let outside = temp_dir("dangling-target");
+        std::os::unix::fs::symlink(outside.join("created.txt"), root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("dir"), root.join("dir")).unwrap();
+
+        let access = FileAccess::ReadWrite(root.clone());
+        assert!(access.resolve("link", true).is_err());
+        assert!(access.resolve("dir/file.txt", true).is_err());
+        assert!(access.resolve("link", false).is_err());
+        fs::remove_dir_all(root).unwrap();
+        fs::remove_dir_all(outside).unwrap();
+    }
+}
@@ -1,10 +1,116 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::bytecodes::Bytecodes;
+use crate::localscope::UpvalueDesc;
+use crate::object::Module;
+use crate::value::{Value, ValueResult};
+use crate::vm::VM;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum FunctionType {
     TypeFunction,
+    TypeMethod,
+    TypeInitializer,
+    // `get name()` and `set name(value)` accessors, a setter returns the value it was given
+    TypeGetter,
+    TypeSetter,
+    TypeGenerator,
+    TypeAsync,
     TypeScript,
 }
 
+/// Declared parameter, calls can pass it by name and leave it out when it has a default.
+#[derive(Debug)]
+pub struct Parameter {
+    pub name: String,
+    pub default: Option<DefaultValue>,
+    // `...name`, the last parameter collecting the extra positional arguments into a list
+    pub rest: bool,
+}
+
+/// Default value of a parameter, compiled as a function of no arguments nested in the function
+/// declaring the parameter. The VM creates its closure in the callee's frame, capturing
+/// `upvalues`, and runs it when a call leaves the parameter out.
+#[derive(Debug)]
+pub struct DefaultValue {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<UpvalueDesc>,
+}
+
+#[derive(Debug)]
 pub struct Function {
-    pub arity: i8,
+    pub arity: u8,
+    pub upvalue_count: u8,
+    // `arity` entries for functions declared with a parameter list
+    pub params: Vec<Parameter>,
+    // calling it returns a generator instead of running the body
+    pub is_generator: bool,
+    // calling it returns a task, run by the scheduler once spawned or awaited
+    pub is_async: bool,
     pub bytecodes: Bytecodes,
     pub name: String,
 }
+
+impl Function {
+    pub fn new(name: &str, arity: u8, upvalue_count: u8, bytecodes: Bytecodes) -> Self {
+        Self {
+            arity,
+            upvalue_count,
+            params: Vec::new(),
+            is_generator: false,
+            is_async: false,
+            bytecodes,
+            name: name.to_string(),
+        }
+    }
+
+    /// Number of leading parameters a call has to pass.
+    pub fn required(&self) -> usize {
+        self.params.iter().take_while(|param| param.default.is_none() && !param.rest).count()
+    }
+
+    pub fn has_rest(&self) -> bool {
+        self.params.last().is_some_and(|param| param.rest)
+    }
+}
+
+/// A variable captured by a closure. It points at a stack slot while the variable is in scope
+/// and holds the value itself once the slot has been popped.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub type UpvalueRef = Rc<RefCell<Upvalue>>;
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<UpvalueRef>,
+    // module whose globals the function sees
+    pub module: Rc<Module>,
+}
+
+impl Closure {
+    pub fn new(function: Rc<Function>, upvalues: Vec<UpvalueRef>, module: Rc<Module>) -> Self {
+        Self { function, upvalues, module }
+    }
+}
+
+/// Signature of a function implemented by the host, it receives the VM and the call arguments.
+pub type NativeFn = fn(&mut VM, &[Value]) -> ValueResult;
+
+#[derive(Debug)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: u8,
+    pub function: NativeFn,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: u8, function: NativeFn) -> Self {
+        Self { name: name.to_string(), arity, function }
+    }
+}
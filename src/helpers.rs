@@ -1,11 +1,103 @@
+use crate::value::ValueRepr;
+
 pub fn is_digit(ch: char) -> bool {
-    return ch.is_digit(10);
+    ch.is_ascii_digit()
 }
 
 pub fn is_alpha(ch: char) -> bool {
-    return ch.is_alphabetic();
+    ch.is_alphabetic()
 }
 
 pub fn is_alpha_num(ch: char) -> bool {
-    return ch.is_alphanumeric();
+    ch.is_alphanumeric()
+}
+
+/// Checks that every `_` separator sits between two digits.
+fn valid_separators(digits: &str, is_digit: fn(char) -> bool) -> bool {
+    let chars: Vec<char> = digits.chars().collect();
+    chars.iter().enumerate().all(|(i, ch)| {
+        *ch != '_' || (i > 0 && i + 1 < chars.len() && is_digit(chars[i - 1]) && is_digit(chars[i + 1]))
+    })
+}
+
+/// Parses a numeric literal as scanned by the lexer: decimal integers and floats with optional
+/// exponent, `0x`/`0b`/`0o` prefixed integers, and `_` digit separators.
+pub fn parse_number(raw: &str) -> Result<ValueRepr, String> {
+    parse_signed_number(raw, false)
+}
+
+/// Parses the literal `raw` preceded by a unary minus, which is how the smallest integer
+/// `-9223372036854775808` is written since its magnitude alone is out of range.
+pub fn parse_negated_number(raw: &str) -> Result<ValueRepr, String> {
+    parse_signed_number(raw, true)
+}
+
+fn parse_signed_number(raw: &str, negated: bool) -> Result<ValueRepr, String> {
+    let invalid = || format!("Invalid numeric literal '{}'.", raw);
+    let out_of_range = || format!("Numeric literal '{}' is out of range.", raw);
+
+    let radix = match raw.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0b" | "0B") => Some(2),
+        Some("0o" | "0O") => Some(8),
+        _ => None,
+    };
+
+    if let Some(radix) = radix {
+        let digits = &raw[2..];
+        let is_radix_digit: fn(char) -> bool = match radix {
+            16 => |ch| ch.is_ascii_hexdigit(),
+            8 => |ch| ('0'..='7').contains(&ch),
+            _ => |ch| ch == '0' || ch == '1',
+        };
+        if digits.is_empty()
+            || !digits.chars().all(|ch| ch == '_' || is_radix_digit(ch))
+            || !valid_separators(digits, is_radix_digit)
+        {
+            return Err(invalid());
+        }
+
+        return match i128::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(v) => signed_integer(v, negated).ok_or_else(out_of_range),
+            Err(_) => Err(out_of_range()),
+        };
+    }
+
+    let well_formed = raw.chars().all(|ch| is_digit(ch) || matches!(ch, '_' | '.' | 'e' | 'E' | '+' | '-'));
+    if !well_formed || !valid_separators(raw, is_digit) {
+        return Err(invalid());
+    }
+
+    let cleaned = raw.replace('_', "");
+    if cleaned.contains(['.', 'e', 'E']) {
+        return match cleaned.parse::<f64>() {
+            Ok(v) if v.is_finite() => Ok(ValueRepr::Number(if negated { -v } else { v })),
+            Ok(_) => Err(out_of_range()),
+            Err(_) => Err(invalid()),
+        };
+    }
+
+    match cleaned.parse::<i128>() {
+        Ok(v) => signed_integer(v, negated).ok_or_else(out_of_range),
+        Err(_) => Err(out_of_range()),
+    }
+}
+
+fn signed_integer(magnitude: i128, negated: bool) -> Option<ValueRepr> {
+    let value = if negated { -magnitude } else { magnitude };
+    i64::try_from(value).ok().map(ValueRepr::Integer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_literals_stop_at_the_i64_bounds() {
+        assert!(matches!(parse_number("9223372036854775807"), Ok(ValueRepr::Integer(i64::MAX))));
+        assert!(parse_number("9223372036854775808").is_err());
+        assert!(matches!(parse_negated_number("9223372036854775808"), Ok(ValueRepr::Integer(i64::MIN))));
+        assert!(matches!(parse_negated_number("0x8000_0000_0000_0000"), Ok(ValueRepr::Integer(i64::MIN))));
+        assert!(parse_negated_number("9223372036854775809").is_err());
+    }
 }
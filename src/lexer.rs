@@ -68,10 +68,7 @@ impl Lexer {
         }
 
         let p = pos.unwrap_or(0);
-        match self.input.get(self.current + p) {
-            None => None,
-            Some(val) => Some(*val as char),
-        }
+        self.input.get(self.current + p).map(|val| *val as char)
     }
 
     fn peek_is(&self, ch: char, pos: Option<usize>) -> bool {
@@ -85,11 +82,11 @@ impl Lexer {
     }
 
     fn peek1(&self) -> Option<char> {
-        return self.peek(None);
+        self.peek(None)
     }
 
     fn peek1_is(&self, ch: char) -> bool {
-        return self.peek_is(ch, None);
+        self.peek_is(ch, None)
     }
 
     fn peek1_is_match(&self, f: fn(ch: char) -> bool) -> bool {
@@ -121,17 +118,37 @@ impl Lexer {
         }
     }
 
-    fn number(&mut self) -> Token {
-        while self.peek1_is_match(is_digit) {
+    fn digits(&mut self) {
+        while self.peek1_is_match(is_digit) || self.peek1_is('_') {
             self.advance();
         }
+    }
 
-        if self.peek1_is('.') && self.peek_is_match(Some(1), is_digit) {
+    /// Scans a numeric literal. The token is kept permissive (any trailing identifier characters are
+    /// included) so that malformed literals are reported as a whole by the parser.
+    fn number(&mut self) -> Token {
+        let radix_prefix = matches!(self.peek1(), Some('x' | 'X' | 'b' | 'B' | 'o' | 'O'));
+        if self.input[self.start] == b'0' && radix_prefix {
             self.advance();
+        } else {
+            self.digits();
 
-            while self.peek1_is_match(is_digit) {
+            if self.peek1_is('.') && self.peek_is_match(Some(1), is_digit) {
                 self.advance();
+                self.digits();
             }
+
+            if self.peek1_is('e') || self.peek1_is('E') {
+                self.advance();
+                if (self.peek1_is('+') || self.peek1_is('-')) && self.peek_is_match(Some(1), is_digit) {
+                    self.advance();
+                }
+                self.digits();
+            }
+        }
+
+        while self.peek1_is_match(is_alpha_num) || self.peek1_is('_') {
+            self.advance();
         }
 
         self.make_token(TokenNumber)
@@ -155,7 +172,7 @@ impl Lexer {
     }
 
     fn ident(&mut self) -> Token {
-        while self.peek1_is_match(is_alpha_num) {
+        while self.peek1_is_match(is_alpha_num) || self.peek1_is('_') {
             self.advance();
         }
 
@@ -165,7 +182,7 @@ impl Lexer {
 
     fn fetch(&self, from: usize, to: usize) -> &str {
         let str = &self.input[from..to];
-        str::from_utf8(&str).unwrap()
+        str::from_utf8(str).unwrap()
     }
 
     fn ident_type(&mut self) -> TokenType {
@@ -185,22 +202,84 @@ impl Lexer {
         if is_digit(ch) {
             return self.number();
         }
-        if is_alpha(ch) {
+        if is_alpha(ch) || ch == '_' {
             return self.ident();
         }
 
-        match ch as char {
+        match ch {
             '(' => self.make_token(TokenLeftParen),
             ')' => self.make_token(TokenRightParen),
             '{' => self.make_token(TokenLeftBrace),
             '}' => self.make_token(TokenRightBrace),
+            '[' => self.make_token(TokenLeftBracket),
+            ']' => self.make_token(TokenRightBracket),
+            ':' => self.make_token(TokenColon),
+            '?' => {
+                let tok_type = if self.next_matches('?') {
+                    TokenQuestionQuestion
+                } else if self.next_matches('.') {
+                    TokenQuestionDot
+                } else {
+                    TokenQuestion
+                };
+                self.make_token(tok_type)
+            }
             ';' => self.make_token(TokenSemicolon),
             ',' => self.make_token(TokenComma),
-            '.' => self.make_token(TokenDot),
-            '-' => self.make_token(TokenMinus),
-            '+' => self.make_token(TokenPlus),
-            '/' => self.make_token(TokenSlash),
-            '*' => self.make_token(TokenStar),
+            '.' => {
+                let tok_type = if !self.next_matches('.') {
+                    TokenDot
+                } else if self.next_matches('=') {
+                    TokenDotDotEqual
+                } else if self.next_matches('.') {
+                    TokenDotDotDot
+                } else {
+                    TokenDotDot
+                };
+                self.make_token(tok_type)
+            }
+            '-' => {
+                let tok_type = match self.next_matches('=') {
+                    true => TokenMinusEqual,
+                    false => TokenMinus,
+                };
+                self.make_token(tok_type)
+            }
+            '+' => {
+                let tok_type = match self.next_matches('=') {
+                    true => TokenPlusEqual,
+                    false => TokenPlus,
+                };
+                self.make_token(tok_type)
+            }
+            '/' => {
+                let tok_type = match self.next_matches('=') {
+                    true => TokenSlashEqual,
+                    false => TokenSlash,
+                };
+                self.make_token(tok_type)
+            }
+            '*' => {
+                let tok_type = if self.next_matches('*') {
+                    TokenStarStar
+                } else if self.next_matches('=') {
+                    TokenStarEqual
+                } else {
+                    TokenStar
+                };
+                self.make_token(tok_type)
+            }
+            '%' => {
+                let tok_type = match self.next_matches('=') {
+                    true => TokenPercentEqual,
+                    false => TokenPercent,
+                };
+                self.make_token(tok_type)
+            }
+            '&' => self.make_token(TokenAmpersand),
+            '|' => self.make_token(TokenPipe),
+            '^' => self.make_token(TokenCaret),
+            '~' => self.make_token(TokenTilde),
             '!' => {
                 let tok_type = match self.next_matches('=') {
                     true => TokenBangEqual,
@@ -209,23 +288,32 @@ impl Lexer {
                 self.make_token(tok_type)
             }
             '=' => {
-                let tok_type = match self.next_matches('=') {
-                    true => TokenEqualEqual,
-                    false => TokenEqual,
+                let tok_type = if self.next_matches('=') {
+                    TokenEqualEqual
+                } else if self.next_matches('>') {
+                    TokenEqualGreater
+                } else {
+                    TokenEqual
                 };
                 self.make_token(tok_type)
             }
             '<' => {
-                let tok_type = match self.next_matches('=') {
-                    true => TokenLessEqual,
-                    false => TokenEqual,
+                let tok_type = if self.next_matches('=') {
+                    TokenLessEqual
+                } else if self.next_matches('<') {
+                    TokenLessLess
+                } else {
+                    TokenLess
                 };
                 self.make_token(tok_type)
             }
             '>' => {
-                let tok_type = match self.next_matches('=') {
-                    true => TokenGreaterEqual,
-                    false => TokenGreater,
+                let tok_type = if self.next_matches('=') {
+                    TokenGreaterEqual
+                } else if self.next_matches('>') {
+                    TokenGreaterGreater
+                } else {
+                    TokenGreater
                 };
                 self.make_token(tok_type)
             }
@@ -251,4 +339,31 @@ impl Iterator for Lexer {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    fn types(source: &str) -> Vec<TokenType> {
+        let mut lex = Lexer::new(source.to_string());
+        let mut types = Vec::new();
+        loop {
+            let tok = lex.scan_next();
+            if tok.is(TokenEof) {
+                return types;
+            }
+            types.push(tok.token_type);
+        }
+    }
+
+    #[test]
+    fn div_is_floor_division() {
+        assert_eq!(types("7 div 2"), vec![TokenNumber, TokenDiv, TokenNumber]);
+        assert_eq!(types("divide"), vec![TokenIdentifier]);
+    }
+
+    #[test]
+    fn slash_slash_is_always_a_comment() {
+        assert_eq!(types("7 // 2"), vec![TokenNumber]);
+        assert_eq!(types("7//2"), vec![TokenNumber]);
+        assert_eq!(types("f()// note\n;"), vec![TokenIdentifier, TokenLeftParen, TokenRightParen, TokenSemicolon]);
+    }
+}
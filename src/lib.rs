@@ -1,38 +1,57 @@
+use std::env;
 use std::fs::File;
 use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use crate::bytecodes::Bytecodes;
 use crate::debug::debug_bytecode;
+use crate::files::FileAccess;
+use crate::manifest::{env_search_paths, Manifest, MANIFEST_NAME};
+use crate::scheduler::Clock;
 use crate::vm::{InterpretResult, VM};
 
 pub mod bytecodes;
+pub mod classes;
 pub mod codegen;
 pub mod compiler;
 pub mod debug;
+pub mod files;
+pub mod function;
 pub mod helpers;
 pub mod lexer;
+pub mod manifest;
+pub mod modules;
+pub mod natives;
+pub mod object;
 pub mod opcode;
 pub mod parser;
 pub mod parser_rules;
+pub mod patterns;
+pub mod scheduler;
 pub mod localscope;
 pub mod token;
 pub mod value;
 pub mod vm;
 pub mod ast;
 
-fn read_file(file_name: &str) -> String {
-    let mut file = File::open(file_name).unwrap();
+fn read_file(file_name: &Path) -> String {
     let mut data = String::new();
-    file.read_to_string(&mut data).unwrap();
+    let read = File::open(file_name).and_then(|mut file| file.read_to_string(&mut data));
+    if let Err(e) = read {
+        eprintln!("Could not read file '{}': {}.", file_name.display(), e);
+        exit(74);
+    }
 
     data
 }
 
-pub fn run_file(file_name: &str) {
+/// Runs the script at `file_name`, the modules it imports are also looked up in `library_paths`.
+/// `file_access` is what the script may do with files.
+pub fn run_file(file_name: &Path, clock: Clock, library_paths: &[PathBuf], file_access: FileAccess) {
     let input = read_file(file_name);
-    let result = {
-        let code = compile(input);
-        run(code)
+    let result = match compile(input) {
+        None => InterpretResult::InterpretCompileError,
+        Some(code) => run(code, file_name, clock, library_paths, file_access),
     };
     match result {
         InterpretResult::InterpretOk => {}
@@ -41,19 +60,54 @@ pub fn run_file(file_name: &str) {
     }
 }
 
-pub fn compile(input: String) -> Bytecodes {
-    let code = compiler::compile(input);
+pub fn compile(input: String) -> Option<Bytecodes> {
+    let code = compiler::compile(input)?;
 
     debug_bytecode(&code, "MAIN");
 
-    code
+    Some(code)
 }
 
-fn run(code: Bytecodes) -> InterpretResult {
+/// Runs the entry script of the project whose manifest is in the current directory or the closest
+/// of its parents, with the library directories of the project. The script may read the files of
+/// the project, and write them too when `allow_write` is set.
+pub fn run_project(clock: Clock, allow_write: bool) {
+    let cwd = env::current_dir().unwrap_or_default();
+    let manifest = match Manifest::find(&cwd) {
+        Some(file) => Manifest::load(&file),
+        None => Err(format!("Could not find {} in '{}' or its parents.", MANIFEST_NAME, cwd.display())),
+    };
+    let manifest = match manifest {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(66);
+        }
+    };
+
+    let mut library_paths = manifest.search_paths();
+    library_paths.extend(env_search_paths());
+    let file_access = FileAccess::under(manifest.root.clone(), allow_write);
+    run_file(&manifest.entry, clock, &library_paths, file_access);
+}
+
+fn run(
+    code: Bytecodes,
+    path: &Path,
+    clock: Clock,
+    library_paths: &[PathBuf],
+    file_access: FileAccess,
+) -> InterpretResult {
     let mut machine = VM::new();
-    machine.interpret(code);
+    machine.set_clock(clock);
+    machine.set_file_access(file_access);
+    machine.set_script_path(path);
+    for dir in library_paths {
+        machine.add_library_path(dir);
+    }
+    let result = machine.interpret(code);
 
     machine.free();
 
-    InterpretResult::InterpretOk
+    result
 }
\ No newline at end of file
@@ -2,6 +2,7 @@ pub struct LocalScope {
     pub local_count: i8,
     pub scope_depth: i8,
     pub locals: Vec<Local>,
+    pub upvalues: Vec<UpvalueDesc>,
 }
 
 pub struct Local {
@@ -9,15 +10,32 @@ pub struct Local {
     depth: i8,
 }
 
+/// Variable of an enclosing function captured by the function being compiled, either one of the
+/// enclosing function's locals or one of its own upvalues.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UpvalueDesc {
+    pub index: u8,
+    pub is_local: bool,
+}
+
 impl Local {
     pub fn new(name: String, depth: i8) -> Self {
         Self { name, depth }
     }
 }
 
+impl Default for LocalScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LocalScope {
     pub fn new() -> Self {
-        Self { local_count: 0, locals: Vec::new(), scope_depth: 0 }
+        let mut scope = Self { local_count: 0, locals: Vec::new(), scope_depth: 0, upvalues: Vec::new() };
+        // slot zero holds the function being called
+        scope.add_local(&String::new());
+        scope
     }
 
     pub fn begin_scope(&mut self) {
@@ -32,6 +50,7 @@ impl LocalScope {
             counts += 1;
             self.local_count -= 1
         }
+        self.locals.truncate(self.local_count as usize);
         counts
     }
 
@@ -41,6 +60,34 @@ impl LocalScope {
         self.locals.push(local);
     }
 
+    /// Removes the locals from `slot` on, to be put back with [`LocalScope::restore`]. A variable is declared
+    /// before its initializer runs, so its slot is not on the stack yet while the initializer is compiled.
+    pub fn take_from(&mut self, slot: usize) -> Vec<Local> {
+        let taken = self.locals.split_off(slot.min(self.locals.len()));
+        self.local_count = self.locals.len() as i8;
+        taken
+    }
+
+    pub fn restore(&mut self, locals: Vec<Local>) {
+        self.locals.extend(locals);
+        self.local_count = self.locals.len() as i8;
+    }
+
+    /// Number of locals declared in scopes nested deeper than `depth`.
+    pub fn count_deeper_than(&self, depth: i8) -> u8 {
+        self.locals.iter().filter(|l| l.depth > depth).count() as u8
+    }
+
+    pub fn add_upvalue(&mut self, index: u8, is_local: bool) -> usize {
+        let upvalue = UpvalueDesc { index, is_local };
+        if let Some(existing) = self.upvalues.iter().position(|u| *u == upvalue) {
+            return existing;
+        }
+
+        self.upvalues.push(upvalue);
+        self.upvalues.len() - 1
+    }
+
     pub fn contains(&self, name: &str) -> bool {
         let mut counter = self.local_count - 1;
 
@@ -64,19 +111,15 @@ impl LocalScope {
         let mut counter = self.local_count - 1;
 
         while counter >= 0 {
-            let l = self.locals.get(counter as usize);
-            match l {
-                None => {}
-                Some(v) => {
-                    if v.name.eq(name) {
-                        return Some(counter as u8);
-                    }
+            if let Some(v) = self.locals.get(counter as usize) {
+                if v.name.eq(name) {
+                    return Some(counter as u8);
                 }
             }
 
             counter -= 1;
         }
 
-        return None;
+        None
     }
 }
@@ -1,24 +1,32 @@
 use std::{env, io};
-use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
 use std::process::exit;
 
 use apoloo::compiler::compile;
+use apoloo::files::FileAccess;
+use apoloo::manifest::env_search_paths;
+use apoloo::scheduler::Clock;
 use apoloo::vm::VM;
 
-fn repl() {
+fn repl(clock: Clock, allow_write: bool) {
     print!("> ");
     io::stdout().flush().unwrap();
 
     let stdin = io::stdin();
 
     let mut machine = VM::new();
+    machine.set_clock(clock);
+    machine.set_file_access(cwd_access(allow_write));
+    for dir in env_search_paths() {
+        machine.add_library_path(&dir);
+    }
 
     for line in stdin.lock().lines() {
         let str = line.unwrap();
-        let code = compile(str);
-
-        machine.interpret(code);
+        if let Some(code) = compile(str) {
+            machine.interpret(code);
+        }
 
         print!("apoloo> ");
         io::stdout().flush().unwrap();
@@ -27,16 +35,52 @@ fn repl() {
     machine.free();
 }
 
+/// Scripts run from the command line may read the files under the current directory, and write
+/// them only with `--allow-write`.
+fn cwd_access(allow_write: bool) -> FileAccess {
+    FileAccess::under(env::current_dir().unwrap_or_default(), allow_write)
+}
+
+/// Removes the option `name` from `args`, telling whether it was there.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(idx) => {
+            args.remove(idx);
+            true
+        }
+        None => false,
+    }
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    // timers run on a virtual clock, `sleep` returns immediately with time skipping ahead
+    let clock = match take_flag(&mut args, "--virtual-clock") {
+        true => Clock::Virtual,
+        false => Clock::Real,
+    };
+    // scripts may only read files unless they are allowed to write them
+    let allow_write = take_flag(&mut args, "--allow-write");
+    // `run` is optional before a path, without one it runs the project around the current directory
+    if args.get(1).is_some_and(|arg| arg == "run") {
+        match args.get(2) {
+            Some(_) => {
+                args.remove(1);
+            }
+            None => {
+                apoloo::run_project(clock, allow_write);
+                return;
+            }
+        }
+    }
     let argc = args.len();
 
     if argc == 1 {
-        repl();
+        repl(clock, allow_write);
     } else if argc == 2 {
-        apoloo::run_file(args.get(1).unwrap());
+        apoloo::run_file(Path::new(args.get(1).unwrap()), clock, &env_search_paths(), cwd_access(allow_write));
     } else {
-        eprintln!("Usage: apoloo [path]");
+        eprintln!("Usage: apoloo [--virtual-clock] [--allow-write] [run] [path]");
         exit(64)
     }
 }
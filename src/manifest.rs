@@ -0,0 +1,201 @@
+use std::env;
+use std::fs;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+
+/// File name of the manifest at the root of a project.
+pub const MANIFEST_NAME: &str = "apoloo.toml";
+
+/// Project described by an `apoloo.toml` file, paths in it are relative to its directory.
+///
+/// ```toml
+/// # comments run to the end of the line
+/// [project]
+/// entry = "src/main.apoloo"
+/// lib = ["lib", "vendor"]
+/// path = "/usr/share/apoloo:/opt/apoloo/lib"
+/// ```
+///
+/// `lib` lists library directories of the project and `path` is a search list in the format of
+/// the `APOLOO_PATH` environment variable, imports not found next to the importing file are
+/// looked up in the former and then in the latter.
+#[derive(Debug)]
+pub struct Manifest {
+    pub root: PathBuf,
+    pub entry: PathBuf,
+    pub lib: Vec<PathBuf>,
+    pub path: Vec<PathBuf>,
+}
+
+enum ManifestValue {
+    String(String),
+    Array(Vec<String>),
+}
+
+impl Manifest {
+    /// Looks for the manifest in `dir` and then in each of its parents.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors().map(|dir| dir.join(MANIFEST_NAME)).find(|file| file.is_file())
+    }
+
+    pub fn load(file: &Path) -> Result<Manifest, String> {
+        let source = fs::read_to_string(file).map_err(|e| format!("Could not read '{}': {}.", file.display(), e))?;
+        let root = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        Manifest::parse(&source, root).map_err(|e| format!("{}: {}", file.display(), e))
+    }
+
+    /// Parses the text of a manifest, an error about a line starts with its number.
+    pub fn parse(source: &str, root: PathBuf) -> Result<Manifest, String> {
+        let mut entry = None;
+        let mut lib = Vec::new();
+        let mut path = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let error = |msg: &str| format!("line {}: {}", index + 1, msg);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                match line {
+                    "[project]" => continue,
+                    _ => return Err(error(&format!("Unknown section {}.", line))),
+                }
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| error("Expect '=' after key."))?;
+            let value = parse_value(value.trim()).map_err(|msg| error(&msg))?;
+            match (key.trim(), value) {
+                ("entry", ManifestValue::String(file)) => entry = Some(root.join(file)),
+                ("lib", ManifestValue::Array(dirs)) => lib = dirs.iter().map(|dir| root.join(dir)).collect(),
+                ("path", ManifestValue::String(list)) => {
+                    path = env::split_paths(&list).map(|dir| root.join(dir)).collect();
+                }
+                ("entry", _) | ("path", _) => return Err(error(&format!("Expect a string for '{}'.", key.trim()))),
+                ("lib", _) => return Err(error("Expect an array of strings for 'lib'.")),
+                (key, _) => return Err(error(&format!("Unknown key '{}'.", key))),
+            }
+        }
+
+        let entry = entry.ok_or("Missing 'entry' script.".to_string())?;
+        Ok(Manifest { root, entry, lib, path })
+    }
+
+    /// Directories imports are looked up in, the library directories first.
+    pub fn search_paths(&self) -> Vec<PathBuf> {
+        self.lib.iter().chain(self.path.iter()).cloned().collect()
+    }
+}
+
+/// Directories listed in the `APOLOO_PATH` environment variable.
+pub fn env_search_paths() -> Vec<PathBuf> {
+    match env::var_os("APOLOO_PATH") {
+        Some(list) => env::split_paths(&list).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Parses a quoted string or a one line array of them, and the comment that may follow.
+fn parse_value(text: &str) -> Result<ManifestValue, String> {
+    let mut chars = text.chars().peekable();
+    let value = match chars.next() {
+        Some('"') => ManifestValue::String(parse_string(&mut chars)?),
+        Some('[') => {
+            let mut items = Vec::new();
+            loop {
+                skip_spaces(&mut chars);
+                match chars.next() {
+                    Some(']') => break,
+                    Some('"') => items.push(parse_string(&mut chars)?),
+                    _ => return Err("Expect string in array.".to_string()),
+                }
+                skip_spaces(&mut chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => break,
+                    _ => return Err("Expect ',' or ']' after array item.".to_string()),
+                }
+            }
+            ManifestValue::Array(items)
+        }
+        _ => return Err("Expect a string or an array as value.".to_string()),
+    };
+
+    skip_spaces(&mut chars);
+    match chars.next() {
+        None | Some('#') => Ok(value),
+        Some(_) => Err("Unexpected text after value.".to_string()),
+    }
+}
+
+/// Reads the rest of a string whose opening quote has been consumed, `\"` and `\\` escape.
+fn parse_string(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match chars.next() {
+                Some(c @ ('"' | '\\')) => string.push(c),
+                _ => return Err("Invalid escape in string.".to_string()),
+            },
+            Some(c) => string.push(c),
+            None => return Err("Unterminated string.".to_string()),
+        }
+    }
+}
+
+fn skip_spaces(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Manifest, String> {
+        Manifest::parse(source, PathBuf::from("/project"))
+    }
+
+    #[test]
+    fn parses_paths_relative_to_the_root() {
+        let source = "# app\n[project]\nentry = \"src/main.apoloo\"  # script\n";
+        let manifest = parse(&format!("{}lib = [\"lib\", \"vendor\"]\npath = \"/opt/apoloo\"\n", source)).unwrap();
+        assert_eq!(manifest.entry, PathBuf::from("/project/src/main.apoloo"));
+        assert_eq!(manifest.search_paths(), ["/project/lib", "/project/vendor", "/opt/apoloo"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let errors = [
+            ("[tool]", "line 1: Unknown section [tool]."),
+            ("entry", "line 1: Expect '=' after key."),
+            ("\nentry = main.apoloo", "line 2: Expect a string or an array as value."),
+            ("entry = [\"a\"]", "line 1: Expect a string for 'entry'."),
+            ("lib = \"lib\"", "line 1: Expect an array of strings for 'lib'."),
+            ("lib = [\"a\" \"b\"]", "line 1: Expect ',' or ']' after array item."),
+            ("name = \"app\"", "line 1: Unknown key 'name'."),
+            ("entry = \"main", "line 1: Unterminated string."),
+            ("entry = \"a\" b", "line 1: Unexpected text after value."),
+            ("lib = []", "Missing 'entry' script."),
+        ];
+        for (source, error) in errors {
+            assert_eq!(parse(source).unwrap_err(), error, "for {:?}", source);
+        }
+    }
+
+    #[test]
+    fn find_walks_up_to_the_nearest_manifest() {
+        let root = std::env::temp_dir().join(format!("apoloo-manifest-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let nested = root.join("app").join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(MANIFEST_NAME), "entry = \"main.apoloo\"").unwrap();
+        assert_eq!(Manifest::find(&nested), Some(root.join(MANIFEST_NAME)));
+
+        fs::write(root.join("app").join(MANIFEST_NAME), "entry = \"main.apoloo\"").unwrap();
+        assert_eq!(Manifest::find(&nested), Some(root.join("app").join(MANIFEST_NAME)));
+        let manifest = Manifest::load(&Manifest::find(&nested).unwrap()).unwrap();
+        assert_eq!(manifest.entry, root.join("app").join("main.apoloo"));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::opcode::OpCode::*;
+use crate::parser::Parser;
+use crate::token::Token;
+use crate::token::TokenType::*;
+
+impl Parser {
+    /// Compiles `import "path" as name;`, binding the module object to `name`.
+    pub fn import_declaration(&mut self) {
+        let path = self.module_path("Expect module path after 'import'.");
+        if self.curr_tok.as_ref().is_some_and(|tok| tok.is(TokenIdentifier) && tok.raw == "as") {
+            self.advance();
+        } else {
+            self.error_at_curr("Expect 'as' after module path.");
+        }
+        let global = self.parse_variable("Expect module name after 'as'.");
+        self.consume(&TokenSemicolon, "Expect ';' after import.");
+
+        self.codegen.emit_op_operand(OpImport, path as u8);
+        self.define_var(global as u8);
+    }
+
+    /// Whether the current token starts `from "path" import ...`, `from` is only a keyword there.
+    pub(crate) fn is_from_import(&self) -> bool {
+        self.curr_tok.as_ref().is_some_and(|tok| tok.is(TokenIdentifier) && tok.raw == "from")
+            && self.peek_next_type() == TokenString
+    }
+
+    /// Compiles `from "path" import a, b;`, binding each name to the definition of the module
+    /// with that name. `from` has already been consumed.
+    pub fn from_import_declaration(&mut self) {
+        let path = self.module_path("Expect module path after 'from'.");
+        self.consume(&TokenImport, "Expect 'import' after module path.");
+        let mut names: Vec<Token> = Vec::new();
+        loop {
+            self.consume(&TokenIdentifier, "Expect name to import.");
+            names.push(self.prev_tok.clone().unwrap());
+            if !self.match_advance(&TokenComma) {
+                break;
+            }
+        }
+        self.consume(&TokenSemicolon, "Expect ';' after import.");
+
+        self.codegen.emit_op_operand(OpImport, path as u8);
+
+        if self.scope.scope_depth > 0 {
+            // the module stays in a hidden local so every name lands in its own slot
+            self.scope.add_local(&"import source".to_string());
+            let source = self.scope.resolve_local("import source").unwrap();
+
+            for name in names {
+                self.codegen.emit_op_operand(OpGetLocal, source);
+                let member = self.name_const(&name.raw);
+                self.codegen.emit_op_operand(OpGetProperty, member as u8);
+                self.add_local(&name);
+            }
+            return;
+        }
+
+        for name in names {
+            self.codegen.emit_op(OpDup);
+            let global = self.name_const(&name.raw);
+            self.codegen.emit_op_operand(OpGetProperty, global as u8);
+            self.define_var(global as u8);
+        }
+        self.codegen.emit_op(OpPop);
+    }
+
+    /// Consumes the string naming a module file and adds its path as a constant.
+    fn module_path(&mut self, msg: &str) -> usize {
+        self.consume(&TokenString, msg);
+        let path = self.prev_tok.as_ref().map(|tok| tok.raw.trim_matches('"').to_owned()).unwrap_or_default();
+        self.name_const(&path)
+    }
+}
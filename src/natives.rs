@@ -0,0 +1,443 @@
+use std::cell::RefCell;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::object::{Iter, Task};
+use crate::value::{Value, ValueRepr, ValueResult};
+use crate::vm::VM;
+
+/// `int(value)` converts floats (truncating), booleans and numeric strings to integers.
+pub fn int(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    match &args[0].0 {
+        ValueRepr::Integer(v) => Ok(Value(ValueRepr::Integer(*v))),
+        ValueRepr::Number(v) => float_to_int(v.trunc()),
+        ValueRepr::Boolean(v) => Ok(Value(ValueRepr::Integer(*v as i64))),
+        ValueRepr::String(v) => match v.trim().parse::<i64>() {
+            Ok(v) => Ok(Value(ValueRepr::Integer(v))),
+            Err(_) => Err(format!("Cannot convert '{}' to int.", v)),
+        },
+        other => Err(format!("Cannot convert {} to int.", other.kind())),
+    }
+}
+
+/// Converts a float with an integral value to an int.
+fn float_to_int(v: f64) -> ValueResult {
+    // i64::MAX is not representable as f64, the bounds are the nearest exclusive powers of two.
+    if (-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&v) {
+        Ok(Value(ValueRepr::Integer(v as i64)))
+    } else {
+        Err(format!("Cannot convert {:?} to int.", v))
+    }
+}
+
+/// `float(value)` converts integers, booleans and numeric strings to floats.
+pub fn float(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    match &args[0].0 {
+        ValueRepr::Number(v) => Ok(Value(ValueRepr::Number(*v))),
+        ValueRepr::Integer(v) => Ok(Value(ValueRepr::Number(*v as f64))),
+        ValueRepr::Boolean(v) => Ok(Value(ValueRepr::Number(*v as i64 as f64))),
+        ValueRepr::String(v) => match v.trim().parse::<f64>() {
+            Ok(v) => Ok(Value(ValueRepr::Number(v))),
+            Err(_) => Err(format!("Cannot convert '{}' to float.", v)),
+        },
+        other => Err(format!("Cannot convert {} to float.", other.kind())),
+    }
+}
+
+/// `str(value)` is the text `print` shows for the value, instances with a `toString` method give its result.
+pub fn str(vm: &mut VM, args: &[Value]) -> ValueResult {
+    match vm.stringify(&args[0]) {
+        Ok(text) => Ok(Value(ValueRepr::String(text))),
+        Err(error) => Err(error.get_property("message").unwrap_or(error).to_string()),
+    }
+}
+
+/// `len(value)` is the number of characters of a string or the number of items of a collection.
+pub fn len(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    let len = match &args[0].0 {
+        ValueRepr::String(v) => v.chars().count(),
+        ValueRepr::List(v) => v.borrow().len(),
+        ValueRepr::Tuple(v) => v.len(),
+        ValueRepr::Map(v) => v.borrow().len(),
+        ValueRepr::Range(v) => {
+            return v.len().map(|len| Value(ValueRepr::Integer(len))).ok_or("Integer overflow.".to_string());
+        }
+        other => return Err(format!("Cannot take the length of {}.", other.kind())),
+    };
+
+    Ok(Value(ValueRepr::Integer(len as i64)))
+}
+
+/// `push(list, value)` appends the value to the end of the list.
+pub fn push(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    match &args[0].0 {
+        ValueRepr::List(v) => {
+            v.borrow_mut().push(args[1].clone());
+            Ok(Value::new())
+        }
+        other => Err(format!("Cannot push onto {}.", other.kind())),
+    }
+}
+
+/// `divmod(a, b)` returns the tuple `(a // b, a % b)`.
+pub fn divmod(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    let quotient = args[0].clone().floor_div(args[1].clone())?;
+    let remainder = (args[0].clone() % args[1].clone())?;
+
+    Ok(Value::tuple(vec![quotient, remainder]))
+}
+
+/// `implements(value, trait)` tells whether the value is an instance of a class mixing in the trait,
+/// or such a class itself.
+pub fn implements(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    let trait_ = match &args[1].0 {
+        ValueRepr::Class(trait_) if trait_.is_trait => trait_,
+        other => return Err(format!("Expected a trait, got {}.", other.kind())),
+    };
+    let implements = match &args[0].0 {
+        ValueRepr::Instance(instance) => instance.class.implements(trait_),
+        ValueRepr::Class(class) => class.implements(trait_),
+        _ => false,
+    };
+
+    Ok(Value(ValueRepr::Boolean(implements)))
+}
+
+/// `sleep(ms)` returns a task completing `ms` milliseconds after it is awaited or spawned.
+pub fn sleep(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    match &args[0].0 {
+        ValueRepr::Integer(ms) if *ms >= 0 => {
+            Ok(Value(ValueRepr::Task(Rc::new(RefCell::new(Task::sleep(*ms as u64))))))
+        }
+        ValueRepr::Integer(_) => Err("Sleep duration must not be negative.".to_string()),
+        other => Err(format!("Sleep duration must be an int, got {}.", other.kind())),
+    }
+}
+
+/// `spawn(task)` starts the task without waiting for it and returns it, so it can be awaited later.
+pub fn spawn(vm: &mut VM, args: &[Value]) -> ValueResult {
+    match &args[0].0 {
+        ValueRepr::Task(task) => {
+            vm.spawn(task);
+            Ok(args[0].clone())
+        }
+        other => Err(format!("Can only spawn tasks, got {}.", other.kind())),
+    }
+}
+
+/// `now()` is the scheduler clock in milliseconds, virtual time when the VM runs on a virtual clock.
+pub fn now(vm: &mut VM, _args: &[Value]) -> ValueResult {
+    Ok(Value(ValueRepr::Integer(vm.now() as i64)))
+}
+
+/// The argument `value` of the math native `name` as a float, ints are converted.
+fn number(name: &str, value: &Value) -> Result<f64, String> {
+    value.as_f64().ok_or_else(|| format!("{}() expects a number, got {}.", name, value.0.kind()))
+}
+
+fn integer(name: &str, value: &Value) -> Result<i64, String> {
+    match &value.0 {
+        ValueRepr::Integer(v) => Ok(*v),
+        other => Err(format!("{}() expects an int, got {}.", name, other.kind())),
+    }
+}
+
+/// Applies `f` to the number argument of the math native `name`, the result is a float.
+fn float_fn(name: &str, value: &Value, f: fn(f64) -> f64) -> ValueResult {
+    Ok(Value(ValueRepr::Number(f(number(name, value)?))))
+}
+
+/// Like [`float_fn`] for functions only defined where `domain` holds, outside it is an error.
+fn float_fn_in(name: &str, value: &Value, domain: fn(f64) -> bool, f: fn(f64) -> f64) -> ValueResult {
+    let x = number(name, value)?;
+    match domain(x) || x.is_nan() {
+        true => Ok(Value(ValueRepr::Number(f(x)))),
+        false => Err(format!("Math domain error in {}().", name)),
+    }
+}
+
+/// Rounds the number argument of `name` to an int with `f`, an int is returned as it is.
+fn round_fn(name: &str, value: &Value, f: fn(f64) -> f64) -> ValueResult {
+    match &value.0 {
+        ValueRepr::Integer(_) => Ok(value.clone()),
+        _ => float_to_int(f(number(name, value)?)),
+    }
+}
+
+/// `sqrt(x)` is the square root of a number that is not negative.
+pub fn sqrt(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn_in("sqrt", &args[0], |x| x >= 0.0, f64::sqrt)
+}
+
+/// `pow(x, y)` is `x ** y`.
+pub fn pow(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    number("pow", &args[0])?;
+    number("pow", &args[1])?;
+    args[0].clone().pow(args[1].clone())
+}
+
+/// `abs(x)` keeps ints as ints.
+pub fn abs(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    match &args[0].0 {
+        ValueRepr::Integer(v) => match v.checked_abs() {
+            Some(v) => Ok(Value(ValueRepr::Integer(v))),
+            None => Err("Integer overflow.".to_string()),
+        },
+        _ => float_fn("abs", &args[0], f64::abs),
+    }
+}
+
+/// `floor(x)` is the greatest int not above `x`.
+pub fn floor(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    round_fn("floor", &args[0], f64::floor)
+}
+
+/// `ceil(x)` is the least int not below `x`.
+pub fn ceil(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    round_fn("ceil", &args[0], f64::ceil)
+}
+
+/// `round(x)` is the int closest to `x`, halves round away from zero.
+pub fn round(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    round_fn("round", &args[0], f64::round)
+}
+
+/// `min(a, b)` is the smaller of two numbers, as it was given.
+pub fn min(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    match number("min", &args[1])? < number("min", &args[0])? {
+        true => Ok(args[1].clone()),
+        false => Ok(args[0].clone()),
+    }
+}
+
+/// `max(a, b)` is the greater of two numbers, as it was given.
+pub fn max(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    match number("max", &args[1])? > number("max", &args[0])? {
+        true => Ok(args[1].clone()),
+        false => Ok(args[0].clone()),
+    }
+}
+
+/// `clamp(x, low, high)` is `x` limited to the range from `low` to `high`.
+pub fn clamp(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    let x = number("clamp", &args[0])?;
+    let (low, high) = (number("clamp", &args[1])?, number("clamp", &args[2])?);
+    if low > high {
+        return Err("clamp() lower bound is greater than the upper bound.".to_string());
+    }
+    match x {
+        _ if x < low => Ok(args[1].clone()),
+        _ if x > high => Ok(args[2].clone()),
+        _ => Ok(args[0].clone()),
+    }
+}
+
+/// `sin(x)`, angles are in radians.
+pub fn sin(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("sin", &args[0], f64::sin)
+}
+
+pub fn cos(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("cos", &args[0], f64::cos)
+}
+
+pub fn tan(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("tan", &args[0], f64::tan)
+}
+
+pub fn asin(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn_in("asin", &args[0], |x| (-1.0..=1.0).contains(&x), f64::asin)
+}
+
+pub fn acos(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn_in("acos", &args[0], |x| (-1.0..=1.0).contains(&x), f64::acos)
+}
+
+pub fn atan(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("atan", &args[0], f64::atan)
+}
+
+/// `atan2(y, x)` is the angle of the point `(x, y)`.
+pub fn atan2(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (y, x) = (number("atan2", &args[0])?, number("atan2", &args[1])?);
+    Ok(Value(ValueRepr::Number(y.atan2(x))))
+}
+
+pub fn sinh(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("sinh", &args[0], f64::sinh)
+}
+
+pub fn cosh(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("cosh", &args[0], f64::cosh)
+}
+
+pub fn tanh(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("tanh", &args[0], f64::tanh)
+}
+
+pub fn asinh(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("asinh", &args[0], f64::asinh)
+}
+
+pub fn acosh(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn_in("acosh", &args[0], |x| x >= 1.0, f64::acosh)
+}
+
+pub fn atanh(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn_in("atanh", &args[0], |x| (-1.0..=1.0).contains(&x), f64::atanh)
+}
+
+/// `log(x)` is the natural logarithm of a positive number.
+pub fn log(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn_in("log", &args[0], |x| x > 0.0, f64::ln)
+}
+
+pub fn log2(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn_in("log2", &args[0], |x| x > 0.0, f64::log2)
+}
+
+pub fn log10(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn_in("log10", &args[0], |x| x > 0.0, f64::log10)
+}
+
+pub fn exp(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    float_fn("exp", &args[0], f64::exp)
+}
+
+/// `is_nan(x)` tells whether a number is the float `nan`, which isn't even equal to itself.
+pub fn is_nan(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    Ok(Value(ValueRepr::Boolean(number("is_nan", &args[0])?.is_nan())))
+}
+
+/// `gcd(a, b)` is the greatest common divisor of two ints, it is never negative.
+pub fn gcd(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (a, b) = (integer("gcd", &args[0])?, integer("gcd", &args[1])?);
+    let gcd = gcd_u64(a.unsigned_abs(), b.unsigned_abs());
+    i64::try_from(gcd).map(|v| Value(ValueRepr::Integer(v))).map_err(|_| "Integer overflow.".to_string())
+}
+
+/// `lcm(a, b)` is the least common multiple of two ints, it is never negative.
+pub fn lcm(_vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (a, b) = (integer("lcm", &args[0])?.unsigned_abs(), integer("lcm", &args[1])?.unsigned_abs());
+    if a == 0 || b == 0 {
+        return Ok(Value(ValueRepr::Integer(0)));
+    }
+    (a / gcd_u64(a, b))
+        .checked_mul(b)
+        .and_then(|v| i64::try_from(v).ok())
+        .map(|v| Value(ValueRepr::Integer(v)))
+        .ok_or("Integer overflow.".to_string())
+}
+
+fn gcd_u64(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn string<'a>(name: &str, value: &'a Value) -> Result<&'a str, String> {
+    match &value.0 {
+        ValueRepr::String(v) => Ok(v),
+        other => Err(format!("{}() expects a string, got {}.", name, other.kind())),
+    }
+}
+
+/// The path argument `value` of the file native `name` and the file it refers to, when the VM lets
+/// scripts read it or, with `write`, also change it.
+fn file_path(vm: &VM, name: &str, value: &Value, write: bool) -> Result<(String, PathBuf), String> {
+    let path = string(name, value)?;
+    Ok((path.to_string(), vm.file_access().resolve(path, write)?))
+}
+
+/// `read_file(path)` is the text of a file.
+pub fn read_file(vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (path, file) = file_path(vm, "read_file", &args[0], false)?;
+    match fs::read_to_string(file) {
+        Ok(text) => Ok(Value(ValueRepr::String(text))),
+        Err(e) => Err(format!("Could not read '{}': {}.", path, e)),
+    }
+}
+
+/// `write_file(path, text)` replaces the contents of a file, creating it when it doesn't exist.
+pub fn write_file(vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (path, file) = file_path(vm, "write_file", &args[0], true)?;
+    let text = string("write_file", &args[1])?;
+    match fs::write(file, text) {
+        Ok(()) => Ok(Value::new()),
+        Err(e) => Err(format!("Could not write '{}': {}.", path, e)),
+    }
+}
+
+/// `append_file(path, text)` adds the text at the end of a file, creating it when it doesn't exist.
+pub fn append_file(vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (path, file) = file_path(vm, "append_file", &args[0], true)?;
+    let text = string("append_file", &args[1])?;
+    let appended = OpenOptions::new().append(true).create(true).open(file).and_then(|mut file| {
+        file.write_all(text.as_bytes())
+    });
+    match appended {
+        Ok(()) => Ok(Value::new()),
+        Err(e) => Err(format!("Could not write '{}': {}.", path, e)),
+    }
+}
+
+/// `read_lines(path)` is an iterator over the lines of a file, without their line endings. The file
+/// is read as the iterator advances.
+pub fn read_lines(vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (path, file) = file_path(vm, "read_lines", &args[0], false)?;
+    match File::open(file) {
+        Ok(file) => {
+            let lines = Iter::Lines { lines: BufReader::new(file).lines(), path };
+            Ok(Value(ValueRepr::Iterator(Rc::new(RefCell::new(lines)))))
+        }
+        Err(e) => Err(format!("Could not read '{}': {}.", path, e)),
+    }
+}
+
+/// `exists(path)` tells whether there is a file or a directory at the path.
+pub fn exists(vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (_, file) = file_path(vm, "exists", &args[0], false)?;
+    Ok(Value(ValueRepr::Boolean(file.exists())))
+}
+
+/// `remove(path)` deletes a file or an empty directory.
+pub fn remove(vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (path, file) = file_path(vm, "remove", &args[0], true)?;
+    let removed = match file.is_dir() {
+        true => fs::remove_dir(file),
+        false => fs::remove_file(file),
+    };
+    match removed {
+        Ok(()) => Ok(Value::new()),
+        Err(e) => Err(format!("Could not remove '{}': {}.", path, e)),
+    }
+}
+
+/// `list_dir(path)` is the list of the names of the entries of a directory, sorted.
+pub fn list_dir(vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (path, dir) = file_path(vm, "list_dir", &args[0], false)?;
+    let names: io::Result<Vec<String>> = fs::read_dir(dir).and_then(|entries| {
+        entries.map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned())).collect()
+    });
+    match names {
+        Ok(mut names) => {
+            names.sort();
+            Ok(Value::list(names.into_iter().map(|name| Value(ValueRepr::String(name))).collect()))
+        }
+        Err(e) => Err(format!("Could not list '{}': {}.", path, e)),
+    }
+}
+
+/// `mkdir(path)` creates a directory and the parents it is missing.
+pub fn mkdir(vm: &mut VM, args: &[Value]) -> ValueResult {
+    let (path, dir) = file_path(vm, "mkdir", &args[0], true)?;
+    match fs::create_dir_all(dir) {
+        Ok(()) => Ok(Value::new()),
+        Err(e) => Err(format!("Could not create '{}': {}.", path, e)),
+    }
+}
@@ -0,0 +1,492 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Lines};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::function::{Closure, UpvalueRef};
+use crate::value::{Value, ValueRepr};
+
+pub type ListRef = Rc<RefCell<Vec<Value>>>;
+pub type MapRef = Rc<RefCell<Map>>;
+
+/// Hashable projection of the values allowed as map keys.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+enum MapKey {
+    Nil,
+    Bool(bool),
+    Integer(i64),
+    Float(u64),
+    String(String),
+    Tuple(Vec<MapKey>),
+    // enum by address, variant index and payload
+    Variant(usize, usize, Vec<MapKey>),
+}
+
+impl MapKey {
+    fn from_value(value: &Value) -> Result<MapKey, String> {
+        match &value.0 {
+            ValueRepr::Nil() => Ok(MapKey::Nil),
+            ValueRepr::Boolean(v) => Ok(MapKey::Bool(*v)),
+            ValueRepr::Integer(v) => Ok(MapKey::Integer(*v)),
+            // floats with an integral value share the key of the equal integer, as `1 == 1.0`
+            ValueRepr::Number(v) if v.fract() == 0.0 && v.abs() < 9.2e18 => Ok(MapKey::Integer(*v as i64)),
+            ValueRepr::Number(v) => Ok(MapKey::Float(v.to_bits())),
+            ValueRepr::String(v) => Ok(MapKey::String(v.to_owned())),
+            ValueRepr::Tuple(items) => {
+                Ok(MapKey::Tuple(items.iter().map(MapKey::from_value).collect::<Result<_, _>>()?))
+            }
+            ValueRepr::Variant(variant) => {
+                let values = variant.values.iter().map(MapKey::from_value).collect::<Result<_, _>>()?;
+                Ok(MapKey::Variant(Rc::as_ptr(&variant.enum_) as usize, variant.index, values))
+            }
+            other => Err(format!("Cannot use {} as a map key.", other.kind())),
+        }
+    }
+}
+
+/// Insertion ordered map, iteration visits keys in the order they were first set.
+#[derive(Debug, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    index: HashMap<MapKey, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<Value>, String> {
+        let key = MapKey::from_value(key)?;
+        Ok(self.index.get(&key).map(|idx| self.entries[*idx].1.clone()))
+    }
+
+    pub fn set(&mut self, key: Value, value: Value) -> Result<(), String> {
+        let hash_key = MapKey::from_value(&key)?;
+        match self.index.get(&hash_key) {
+            Some(idx) => self.entries[*idx].1 = value,
+            None => {
+                self.index.insert(hash_key, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn keys(&self) -> Vec<Value> {
+        self.entries.iter().map(|(k, _)| k.clone()).collect()
+    }
+
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Integer range, `start..end` or `start..=end`, with an optional `step`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: i64, end: i64, step: i64, inclusive: bool) -> Result<Self, String> {
+        if step == 0 {
+            return Err("Range step cannot be zero.".to_string());
+        }
+
+        Ok(Self { start, end, step, inclusive })
+    }
+
+    fn in_bounds(&self, value: i64) -> bool {
+        match (self.step > 0, self.inclusive) {
+            (true, true) => value <= self.end,
+            (true, false) => value < self.end,
+            (false, true) => value >= self.end,
+            (false, false) => value > self.end,
+        }
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let from_start = (value >= self.start && self.step > 0) || (value <= self.start && self.step < 0);
+        from_start && self.in_bounds(value) && (value as i128 - self.start as i128) % self.step as i128 == 0
+    }
+
+    /// Number of values in the range, `None` when it doesn't fit an integer.
+    pub fn len(&self) -> Option<i64> {
+        if self.is_empty() {
+            return Some(0);
+        }
+
+        let end = self.end as i128 - if self.inclusive { 0 } else { self.step.signum() as i128 };
+        i64::try_from((end - self.start as i128) / self.step as i128 + 1).ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.in_bounds(self.start)
+    }
+}
+
+/// State of a `for-in` loop over one of the built-in iterable values.
+#[derive(Debug)]
+pub enum Iter {
+    Range { next: Option<i64>, range: Range },
+    List { list: ListRef, index: usize },
+    Tuple { items: Rc<Vec<Value>>, index: usize },
+    Keys { keys: Vec<Value>, index: usize },
+    Chars { chars: Vec<char>, index: usize },
+    // lines of a file read as the loop goes, `path` is how the script named the file
+    Lines { lines: Lines<BufReader<File>>, path: String },
+}
+
+impl Iter {
+    pub fn from_value(value: &Value) -> Result<Iter, String> {
+        match &value.0 {
+            ValueRepr::Range(range) => Ok(Iter::Range { next: Some(range.start), range: *range }),
+            ValueRepr::List(list) => Ok(Iter::List { list: list.clone(), index: 0 }),
+            ValueRepr::Tuple(items) => Ok(Iter::Tuple { items: items.clone(), index: 0 }),
+            ValueRepr::Map(map) => Ok(Iter::Keys { keys: map.borrow().keys(), index: 0 }),
+            ValueRepr::String(str) => Ok(Iter::Chars { chars: str.chars().collect(), index: 0 }),
+            other => Err(format!("Cannot iterate over {}.", other.kind())),
+        }
+    }
+
+    /// Next item of the loop, reading the next line of a file can fail.
+    pub fn try_next(&mut self) -> Result<Option<Value>, String> {
+        match self {
+            Iter::Lines { lines, path } => match lines.next() {
+                Some(Ok(line)) => Ok(Some(Value(ValueRepr::String(line)))),
+                Some(Err(e)) => Err(format!("Could not read '{}': {}.", path, e)),
+                None => Ok(None),
+            },
+            _ => Ok(self.next()),
+        }
+    }
+}
+
+impl Iterator for Iter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            Iter::Range { next, range } => {
+                let current = next.filter(|v| range.in_bounds(*v))?;
+                *next = current.checked_add(range.step);
+                Some(Value(ValueRepr::Integer(current)))
+            }
+            Iter::List { list, index } => {
+                let value = list.borrow().get(*index).cloned()?;
+                *index += 1;
+                Some(value)
+            }
+            Iter::Tuple { items, index } => {
+                let value = items.get(*index).cloned()?;
+                *index += 1;
+                Some(value)
+            }
+            Iter::Keys { keys, index } => {
+                let value = keys.get(*index).cloned()?;
+                *index += 1;
+                Some(value)
+            }
+            Iter::Chars { chars, index } => {
+                let value = chars.get(*index)?.to_string();
+                *index += 1;
+                Some(Value(ValueRepr::String(value)))
+            }
+            Iter::Lines { lines, .. } => lines.next()?.ok().map(|line| Value(ValueRepr::String(line))),
+        }
+    }
+}
+
+/// Methods or accessors of a class by name.
+pub type Members = RefCell<HashMap<String, Rc<Closure>>>;
+
+/// Class declared with `class`, a subclass starts with a copy of its superclass' members. A trait
+/// declared with `trait` is a class that can't be instantiated, classes mix its members in.
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub is_trait: bool,
+    // traits mixed into the class or one of its superclasses
+    pub traits: RefCell<Vec<Rc<Class>>>,
+    // traits listed in the class's own `with`, no two of them may define the same member
+    pub mixins: RefCell<Vec<Rc<Class>>>,
+    pub methods: Members,
+    pub getters: Members,
+    pub setters: Members,
+    // initializers of the fields every instance starts with, run in order before `init`
+    pub fields: RefCell<Vec<(String, Rc<Closure>)>>,
+    // static methods and fields, read and assigned on the class object
+    pub statics: RefCell<HashMap<String, Value>>,
+}
+
+impl Class {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            is_trait: false,
+            traits: RefCell::new(Vec::new()),
+            mixins: RefCell::new(Vec::new()),
+            methods: RefCell::new(HashMap::new()),
+            getters: RefCell::new(HashMap::new()),
+            setters: RefCell::new(HashMap::new()),
+            fields: RefCell::new(Vec::new()),
+            statics: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn new_trait(name: &str) -> Self {
+        Self { is_trait: true, ..Self::new(name) }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<Closure>> {
+        self.methods.borrow().get(name).cloned()
+    }
+
+    /// Copies the members of `superclass` into this class, members declared later override them.
+    pub fn inherit(&self, superclass: &Class) {
+        self.methods.borrow_mut().extend(superclass.methods.borrow().clone());
+        self.getters.borrow_mut().extend(superclass.getters.borrow().clone());
+        self.setters.borrow_mut().extend(superclass.setters.borrow().clone());
+        self.fields.borrow_mut().extend(superclass.fields.borrow().iter().cloned());
+        self.statics.borrow_mut().extend(superclass.statics.borrow().clone());
+        self.traits.borrow_mut().extend(superclass.traits.borrow().iter().cloned());
+    }
+
+    /// Copies the members of `trait_` into this class, like `inherit`, and records that the class
+    /// implements it. Fails when another trait the class mixes in defines one of its members.
+    pub fn mix_in(&self, trait_: &Rc<Class>) -> Result<(), String> {
+        let members = trait_.member_names();
+        for mixin in self.mixins.borrow().iter().filter(|mixin| !Rc::ptr_eq(mixin, trait_)) {
+            let other = mixin.member_names();
+            if let Some(member) = members.iter().filter(|member| other.contains(member)).min() {
+                return Err(format!(
+                    "Method '{}' is defined in both traits {} and {}.",
+                    member, mixin.name, trait_.name
+                ));
+            }
+        }
+
+        self.inherit(trait_);
+        self.traits.borrow_mut().push(trait_.clone());
+        self.mixins.borrow_mut().push(trait_.clone());
+        Ok(())
+    }
+
+    /// Names of the methods, accessors, fields and statics declared in the class.
+    fn member_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.methods.borrow().keys().cloned().collect();
+        names.extend(self.getters.borrow().keys().cloned());
+        names.extend(self.setters.borrow().keys().cloned());
+        names.extend(self.fields.borrow().iter().map(|(name, _)| name.clone()));
+        names.extend(self.statics.borrow().keys().cloned());
+        names
+    }
+
+    /// Whether the class mixes in `trait_`, directly or through a superclass.
+    pub fn implements(&self, trait_: &Rc<Class>) -> bool {
+        self.traits.borrow().iter().any(|implemented| Rc::ptr_eq(implemented, trait_))
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: RefCell<HashMap<String, Value>>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Self { class, fields: RefCell::new(HashMap::new()) }
+    }
+}
+
+/// Enumeration declared with `enum`, its variants are read as properties of it, `Color.Red`.
+#[derive(Debug)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<VariantDecl>,
+}
+
+/// Variant declared in an enum, `fields` names the payload of a variant like `Circle(r)`.
+#[derive(Debug)]
+pub struct VariantDecl {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+/// Value of an enum variant. A variant taking a payload read without one, `Shape.Circle`, is
+/// its constructor.
+#[derive(Debug)]
+pub struct Variant {
+    pub enum_: Rc<Enum>,
+    pub index: usize,
+    pub values: Vec<Value>,
+}
+
+impl Variant {
+    pub fn new(enum_: Rc<Enum>, index: usize, values: Vec<Value>) -> Self {
+        Self { enum_, index, values }
+    }
+
+    pub fn decl(&self) -> &VariantDecl {
+        &self.enum_.variants[self.index]
+    }
+
+    pub fn is_constructor(&self) -> bool {
+        self.values.len() != self.decl().fields.len()
+    }
+}
+
+/// Top-level definitions of a script file, functions declared in the file read and define their
+/// globals in it. An imported module is read with properties, `strings.upper`.
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    // canonical path of the file, `None` for code typed into the REPL
+    pub path: Option<PathBuf>,
+    pub globals: RefCell<HashMap<String, Value>>,
+}
+
+impl Module {
+    pub fn new(name: &str, path: Option<PathBuf>) -> Self {
+        Self { name: name.to_string(), path, globals: RefCell::new(HashMap::new()) }
+    }
+}
+
+/// Method read off an instance, calling it runs the method with `this` set to `receiver`.
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+impl BoundMethod {
+    pub fn new(receiver: Value, method: Rc<Closure>) -> Self {
+        Self { receiver, method }
+    }
+}
+
+/// Method of a built-in value, such as a generator's `next`, the VM implements it by name.
+#[derive(Debug)]
+pub struct BuiltinMethod {
+    pub receiver: Value,
+    pub name: String,
+}
+
+impl BuiltinMethod {
+    pub fn new(receiver: Value, name: &str) -> Self {
+        Self { receiver, name: name.to_string() }
+    }
+}
+
+pub type GeneratorRef = Rc<RefCell<Generator>>;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GeneratorState {
+    Suspended,
+    Running,
+    Done,
+}
+
+/// `try` block open in a suspended generator, `stack_offset` is relative to the generator's frame.
+#[derive(Debug)]
+pub struct SuspendedHandler {
+    pub stack_offset: usize,
+    pub catch_ip: usize,
+    pub finally_ip: Option<usize>,
+}
+
+/// Call of a `fun*` function. While suspended it holds the frame's stack segment, starting with
+/// the callee and its arguments, and the `ip` to resume at.
+#[derive(Debug)]
+pub struct Generator {
+    pub closure: Rc<Closure>,
+    pub stack: Vec<Value>,
+    pub ip: usize,
+    pub handlers: Vec<SuspendedHandler>,
+    // upvalues closed over the saved stack and their offsets, they reopen when the frame resumes
+    pub upvalues: Vec<(usize, UpvalueRef)>,
+    // value `done()` ran ahead to, the next `next()` hands it out
+    pub peeked: Option<Value>,
+    pub state: GeneratorState,
+    // parameters the call left out, their defaults are evaluated when the body starts
+    pub missing: Vec<u8>,
+}
+
+impl Generator {
+    pub fn new(closure: Rc<Closure>, stack: Vec<Value>, missing: Vec<u8>) -> Self {
+        Self {
+            closure,
+            stack,
+            ip: 0,
+            handlers: Vec::new(),
+            upvalues: Vec::new(),
+            peeked: None,
+            state: GeneratorState::Suspended,
+            missing,
+        }
+    }
+}
+
+pub type TaskRef = Rc<RefCell<Task>>;
+
+/// Call of an `async fun`, or a `sleep` timer when `coroutine` is `None`. It does nothing until it
+/// is spawned or awaited, then the scheduler runs it interleaved with the other tasks.
+#[derive(Debug)]
+pub struct Task {
+    pub name: String,
+    pub coroutine: Option<Generator>,
+    // milliseconds a timer waits once started
+    pub delay: u64,
+    pub started: bool,
+    // returned value, or the value thrown out of the task
+    pub outcome: Option<Result<Value, Value>>,
+    // tasks suspended in `await` on this one, resumed once it completes
+    pub waiters: Vec<TaskRef>,
+    // task this one is suspended on, its outcome is handed over on resume
+    pub awaiting: Option<TaskRef>,
+}
+
+impl Task {
+    pub fn new(closure: Rc<Closure>, stack: Vec<Value>, missing: Vec<u8>) -> Self {
+        let name = closure.function.name.clone();
+        Self::with(name, Some(Generator::new(closure, stack, missing)), 0)
+    }
+
+    pub fn sleep(delay: u64) -> Self {
+        Self::with("sleep".to_string(), None, delay)
+    }
+
+    fn with(name: String, coroutine: Option<Generator>, delay: u64) -> Self {
+        Self { name, coroutine, delay, started: false, outcome: None, waiters: Vec::new(), awaiting: None }
+    }
+}
+
+/// Resolves a possibly negative index against a sequence of `len` items.
+pub fn sequence_index(index: &Value, len: usize) -> Result<usize, String> {
+    let idx = match index.0 {
+        ValueRepr::Integer(v) => v,
+        _ => return Err("Index must be an integer.".to_string()),
+    };
+
+    let resolved = if idx < 0 { idx + len as i64 } else { idx };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(format!("Index {} out of bounds for length {}.", idx, len));
+    }
+
+    Ok(resolved as usize)
+}
@@ -29,9 +29,69 @@ pub enum OpCode {
     OpSetLocal = 22,
     OpJump = 23,
     OpLoop = 24,
+    OpFloorDivide = 25,
+    OpCall = 26,
+    OpModulo = 27,
+    OpPower = 28,
+    OpBitAnd = 29,
+    OpBitOr = 30,
+    OpBitXor = 31,
+    OpBitNot = 32,
+    OpShiftLeft = 33,
+    OpShiftRight = 34,
+    OpBuildList = 35,
+    OpBuildMap = 36,
+    OpIndexGet = 37,
+    OpIndexSet = 38,
+    OpRange = 39,
+    OpIterInit = 40,
+    OpIterNext = 41,
+    OpGetUpvalue = 42,
+    OpSetUpvalue = 43,
+    OpClosure = 44,
+    OpBuildTuple = 45,
+    OpUnpack = 46,
+    OpDup = 47,
+    OpInRange = 48,
+    OpJumpIfNil = 49,
+    OpClass = 50,
+    OpGetProperty = 51,
+    OpSetProperty = 52,
+    OpMethod = 53,
+    OpInherit = 54,
+    OpGetSuper = 55,
+    OpDup2 = 56,
+    OpTry = 57,
+    OpPopTry = 58,
+    OpThrow = 59,
+    OpEndFinally = 60,
+    OpYield = 61,
+    OpAwait = 62,
+    OpCallNamed = 63,
+    OpSpread = 64,
+    OpCallSpread = 65,
+    OpTailCall = 66,
+    OpGetter = 67,
+    OpSetter = 68,
+    OpField = 69,
+    OpStatic = 70,
+    OpTrait = 71,
+    OpMixin = 72,
+    OpMatchVariant = 73,
+    OpImport = 74,
+    OpUnpackReversed = 75,
+    OpLeave = 76,
+    OpIterClose = 77,
     OpUnKnown = 99,
 }
 
+/// How the code guarded by a `finally` block completed, `OpEndFinally` resumes it once the block ran.
+/// The completed value sits below the kind on the stack.
+pub const FINALLY_NORMAL: i64 = 0;
+pub const FINALLY_THROW: i64 = 1;
+pub const FINALLY_RETURN: i64 = 2;
+pub const FINALLY_JUMP: i64 = 3;
+
 impl From<u8> for OpCode {
     fn from(op: u8) -> Self {
         match op {
@@ -60,6 +120,59 @@ impl From<u8> for OpCode {
             22 => OpCode::OpSetLocal,
             23 => OpCode::OpJump,
             24 => OpCode::OpLoop,
+            25 => OpCode::OpFloorDivide,
+            26 => OpCode::OpCall,
+            27 => OpCode::OpModulo,
+            28 => OpCode::OpPower,
+            29 => OpCode::OpBitAnd,
+            30 => OpCode::OpBitOr,
+            31 => OpCode::OpBitXor,
+            32 => OpCode::OpBitNot,
+            33 => OpCode::OpShiftLeft,
+            34 => OpCode::OpShiftRight,
+            35 => OpCode::OpBuildList,
+            36 => OpCode::OpBuildMap,
+            37 => OpCode::OpIndexGet,
+            38 => OpCode::OpIndexSet,
+            39 => OpCode::OpRange,
+            40 => OpCode::OpIterInit,
+            41 => OpCode::OpIterNext,
+            42 => OpCode::OpGetUpvalue,
+            43 => OpCode::OpSetUpvalue,
+            44 => OpCode::OpClosure,
+            45 => OpCode::OpBuildTuple,
+            46 => OpCode::OpUnpack,
+            47 => OpCode::OpDup,
+            48 => OpCode::OpInRange,
+            49 => OpCode::OpJumpIfNil,
+            50 => OpCode::OpClass,
+            51 => OpCode::OpGetProperty,
+            52 => OpCode::OpSetProperty,
+            53 => OpCode::OpMethod,
+            54 => OpCode::OpInherit,
+            55 => OpCode::OpGetSuper,
+            56 => OpCode::OpDup2,
+            57 => OpCode::OpTry,
+            58 => OpCode::OpPopTry,
+            59 => OpCode::OpThrow,
+            60 => OpCode::OpEndFinally,
+            61 => OpCode::OpYield,
+            62 => OpCode::OpAwait,
+            63 => OpCode::OpCallNamed,
+            64 => OpCode::OpSpread,
+            65 => OpCode::OpCallSpread,
+            66 => OpCode::OpTailCall,
+            67 => OpCode::OpGetter,
+            68 => OpCode::OpSetter,
+            69 => OpCode::OpField,
+            70 => OpCode::OpStatic,
+            71 => OpCode::OpTrait,
+            72 => OpCode::OpMixin,
+            73 => OpCode::OpMatchVariant,
+            74 => OpCode::OpImport,
+            75 => OpCode::OpUnpackReversed,
+            76 => OpCode::OpLeave,
+            77 => OpCode::OpIterClose,
             99 => OpCode::OpUnKnown,
             o => {
                 eprintln!("Opcode '{}' not found. Compiler error", o);
@@ -69,6 +182,54 @@ impl From<u8> for OpCode {
     }
 }
 
+impl OpCode {
+    /// Values the instruction pushes minus the values it pops, `operand` is its first operand. Jumps
+    /// count as falling through, so `OpIterNext` pushes the next item.
+    pub fn stack_effect(&self, operand: u8) -> i32 {
+        let n = operand as i32;
+        match self {
+            OpCode::OpConstant
+            | OpCode::OpNil
+            | OpCode::OpTrue
+            | OpCode::OpFalse
+            | OpCode::OpGetGlobal
+            | OpCode::OpGetLocal
+            | OpCode::OpGetUpvalue
+            | OpCode::OpClosure
+            | OpCode::OpDup
+            | OpCode::OpClass
+            | OpCode::OpTrait
+            | OpCode::OpImport
+            | OpCode::OpIterNext => 1,
+            OpCode::OpDup2 => 2,
+            OpCode::OpNegate
+            | OpCode::OpNot
+            | OpCode::OpBitNot
+            | OpCode::OpSetGlobal
+            | OpCode::OpSetLocal
+            | OpCode::OpSetUpvalue
+            | OpCode::OpJumpIfFalse
+            | OpCode::OpJumpIfNil
+            | OpCode::OpJump
+            | OpCode::OpLoop
+            | OpCode::OpIterInit
+            | OpCode::OpGetProperty
+            | OpCode::OpTry
+            | OpCode::OpPopTry
+            | OpCode::OpAwait
+            | OpCode::OpUnKnown => 0,
+            OpCode::OpIndexSet | OpCode::OpMixin | OpCode::OpEndFinally => -2,
+            OpCode::OpPopN | OpCode::OpLeave | OpCode::OpCall | OpCode::OpTailCall | OpCode::OpCallNamed => -n,
+            OpCode::OpBuildList | OpCode::OpBuildTuple => 1 - n,
+            OpCode::OpBuildMap => 1 - 2 * n,
+            // flag 2 says whether a step is on the stack
+            OpCode::OpRange => -1 - (n >> 1 & 1),
+            OpCode::OpUnpack | OpCode::OpUnpackReversed | OpCode::OpMatchVariant => n - 1,
+            _ => -1,
+        }
+    }
+}
+
 impl From<OpCode> for u8 {
     fn from(op: OpCode) -> Self {
         op as u8
@@ -107,6 +268,59 @@ impl fmt::Display for OpCode {
                 OpCode::OpPopN => "OP_POP_N",
                 OpCode::OpJump => "OP_JUMP",
                 OpCode::OpLoop => "OP_LOOP",
+                OpCode::OpFloorDivide => "OP_FLOOR_DIVIDE",
+                OpCode::OpCall => "OP_CALL",
+                OpCode::OpModulo => "OP_MODULO",
+                OpCode::OpPower => "OP_POWER",
+                OpCode::OpBitAnd => "OP_BIT_AND",
+                OpCode::OpBitOr => "OP_BIT_OR",
+                OpCode::OpBitXor => "OP_BIT_XOR",
+                OpCode::OpBitNot => "OP_BIT_NOT",
+                OpCode::OpShiftLeft => "OP_SHIFT_LEFT",
+                OpCode::OpShiftRight => "OP_SHIFT_RIGHT",
+                OpCode::OpBuildList => "OP_BUILD_LIST",
+                OpCode::OpBuildMap => "OP_BUILD_MAP",
+                OpCode::OpIndexGet => "OP_INDEX_GET",
+                OpCode::OpIndexSet => "OP_INDEX_SET",
+                OpCode::OpRange => "OP_RANGE",
+                OpCode::OpIterInit => "OP_ITER_INIT",
+                OpCode::OpIterNext => "OP_ITER_NEXT",
+                OpCode::OpGetUpvalue => "OP_GET_UPVALUE",
+                OpCode::OpSetUpvalue => "OP_SET_UPVALUE",
+                OpCode::OpClosure => "OP_CLOSURE",
+                OpCode::OpBuildTuple => "OP_BUILD_TUPLE",
+                OpCode::OpUnpack => "OP_UNPACK",
+                OpCode::OpDup => "OP_DUP",
+                OpCode::OpInRange => "OP_IN_RANGE",
+                OpCode::OpJumpIfNil => "OP_JUMP_IF_NIL",
+                OpCode::OpClass => "OP_CLASS",
+                OpCode::OpGetProperty => "OP_GET_PROPERTY",
+                OpCode::OpSetProperty => "OP_SET_PROPERTY",
+                OpCode::OpMethod => "OP_METHOD",
+                OpCode::OpInherit => "OP_INHERIT",
+                OpCode::OpGetSuper => "OP_GET_SUPER",
+                OpCode::OpDup2 => "OP_DUP2",
+                OpCode::OpTry => "OP_TRY",
+                OpCode::OpPopTry => "OP_POP_TRY",
+                OpCode::OpThrow => "OP_THROW",
+                OpCode::OpEndFinally => "OP_END_FINALLY",
+                OpCode::OpYield => "OP_YIELD",
+                OpCode::OpAwait => "OP_AWAIT",
+                OpCode::OpCallNamed => "OP_CALL_NAMED",
+                OpCode::OpSpread => "OP_SPREAD",
+                OpCode::OpCallSpread => "OP_CALL_SPREAD",
+                OpCode::OpTailCall => "OP_TAIL_CALL",
+                OpCode::OpGetter => "OP_GETTER",
+                OpCode::OpSetter => "OP_SETTER",
+                OpCode::OpField => "OP_FIELD",
+                OpCode::OpStatic => "OP_STATIC",
+                OpCode::OpTrait => "OP_TRAIT",
+                OpCode::OpMixin => "OP_MIXIN",
+                OpCode::OpMatchVariant => "OP_MATCH_VARIANT",
+                OpCode::OpImport => "OP_IMPORT",
+                OpCode::OpUnpackReversed => "OP_UNPACK_REVERSED",
+                OpCode::OpLeave => "OP_LEAVE",
+                OpCode::OpIterClose => "OP_ITER_CLOSE",
             }
         )
     }
@@ -1,25 +1,46 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::codegen::Codegen;
+use crate::compiler::{ClassContext, Compiler, LoopContext};
+use crate::function::{DefaultValue, Function, FunctionType, Parameter};
+use crate::helpers::{parse_negated_number, parse_number};
 use crate::lexer::Lexer;
-use crate::localscope::LocalScope;
+use crate::localscope::{Local, LocalScope, UpvalueDesc};
+use crate::opcode::{OpCode, FINALLY_NORMAL, FINALLY_THROW};
 use crate::opcode::OpCode::*;
 use crate::parser_rules::{ParsePrecedence, ParseRule};
 use crate::parser_rules::ParsePrecedence::*;
 use crate::token::{Token, TokenType};
 use crate::token::TokenType::*;
+use crate::value::{Value, ValueRepr};
 
 pub struct Parser {
     pub lex: Lexer,
     pub codegen: Codegen,
-    curr_tok: Option<Token>,
-    prev_tok: Option<Token>,
+    pub(crate) curr_tok: Option<Token>,
+    pub(crate) prev_tok: Option<Token>,
     pub parse_rules: HashMap<TokenType, ParseRule>,
     pub had_error: bool,
     pub scope: LocalScope,
+    pub function_type: FunctionType,
+    // compilers of the functions enclosing the one being compiled, innermost last
+    pub enclosing: Vec<Compiler>,
+    // loops enclosing the current statement, innermost last
+    pub loops: Vec<LoopContext>,
+    // label parsed in front of the loop statement about to be compiled
+    pending_label: Option<String>,
+    // classes enclosing the code being compiled, innermost last
+    pub classes: Vec<ClassContext>,
+    // member names of the global traits declared so far, to catch classes mixing in conflicting traits
+    pub traits: HashMap<String, Vec<String>>,
+    // try blocks whose handler is active at the current point of the function
+    pub tries: usize,
+    // jumps taken by `?.` on nil, patched where the access chain they belong to ends
+    optional_chain: Vec<usize>,
 }
 
-impl<'a> Parser {
+impl Parser {
     pub fn new(lex: Lexer) -> Parser {
         let mut p = Parser {
             lex,
@@ -29,6 +50,14 @@ impl<'a> Parser {
             had_error: false,
             parse_rules: HashMap::new(),
             scope: LocalScope::new(),
+            function_type: FunctionType::TypeScript,
+            enclosing: Vec::new(),
+            loops: Vec::new(),
+            pending_label: None,
+            classes: Vec::new(),
+            traits: HashMap::new(),
+            tries: 0,
+            optional_chain: Vec::new(),
         };
 
         p.parse_rules = p.rules();
@@ -36,12 +65,20 @@ impl<'a> Parser {
         p
     }
 
-    fn curr_is(&self, tok_type: &TokenType) -> bool {
+    pub(crate) fn curr_is(&self, tok_type: &TokenType) -> bool {
         self.curr_tok_type().is(tok_type)
     }
 
+    /// Type of the token following the current one, scanned on a copy of the lexer.
+    pub(crate) fn peek_next_type(&self) -> TokenType {
+        self.lex.clone().scan_next().token_type
+    }
+
     pub fn advance(&mut self) {
         self.prev_tok = self.curr_tok.clone();
+        if let Some(prev) = &self.prev_tok {
+            self.codegen.line = prev.line;
+        }
 
         loop {
             self.curr_tok = Some(self.lex.scan_next());
@@ -56,6 +93,12 @@ impl<'a> Parser {
     }
 
     pub fn emit_return(&mut self) -> usize {
+        match self.function_type {
+            // initializers always return the instance
+            FunctionType::TypeInitializer => self.codegen.emit_op_operand(OpGetLocal, 0),
+            FunctionType::TypeSetter => self.codegen.emit_op_operand(OpGetLocal, 1),
+            _ => self.codegen.emit_op(OpNil),
+        };
         self.codegen.emit_return()
     }
 
@@ -78,30 +121,57 @@ impl<'a> Parser {
     }
 
     pub fn prev_tok_type(&self) -> TokenType {
-        match &self.prev_tok.clone() {
-            Some(tok) => Some(&tok.token_type),
-            None => None,
-            _ => None,
-        }
-            .unwrap()
-            .clone()
+        self.prev_tok.as_ref().unwrap().token_type.clone()
     }
 
     pub fn curr_tok_type(&self) -> TokenType {
-        match &self.curr_tok.clone() {
-            Some(tok) => Some(&tok.token_type),
-            None => None,
-            _ => None,
-        }
-            .unwrap()
-            .clone()
+        self.curr_tok.as_ref().unwrap().token_type.clone()
     }
 
-    pub fn grouping(&mut self) {
+    pub fn grouping(&mut self, can_assign: bool) {
+        if self.is_arrow_lambda() {
+            self.arrow_lambda();
+            return;
+        }
+        if can_assign && self.is_destructuring_assignment() {
+            self.destructuring_assignment();
+            return;
+        }
+
+        if self.match_advance(&TokenRightParen) {
+            self.codegen.emit_op_operand(OpBuildTuple, 0);
+            return;
+        }
+
         self.expression();
+
+        if self.match_advance(&TokenComma) {
+            self.tuple();
+            return;
+        }
+
         self.consume(&TokenRightParen, "Expect ')' after expression.")
     }
 
+    /// Compiles the rest of a tuple literal after its first item and comma, `(a,)` is a one-tuple.
+    fn tuple(&mut self) {
+        let mut count: u8 = 1;
+        while !self.curr_is(&TokenRightParen) && !self.curr_is(&TokenEof) {
+            self.expression();
+            if count == u8::MAX {
+                self.error("Can't have more than 255 items in a tuple.");
+            }
+            count = count.wrapping_add(1);
+
+            if !self.match_advance(&TokenComma) {
+                break;
+            }
+        }
+
+        self.consume(&TokenRightParen, "Expect ')' after tuple items.");
+        self.codegen.emit_op_operand(OpBuildTuple, count);
+    }
+
     pub fn expression(&mut self) {
         self.parse(&PrecedenceAssignment);
     }
@@ -115,6 +185,19 @@ impl<'a> Parser {
     }
 
     pub fn var_declaration(&mut self) {
+        if self.match_advance(&TokenLeftParen) {
+            self.sequence_pattern_declaration(TokenRightParen);
+            return;
+        }
+        if self.match_advance(&TokenLeftBracket) {
+            self.sequence_pattern_declaration(TokenRightBracket);
+            return;
+        }
+        if self.match_advance(&TokenLeftBrace) {
+            self.map_pattern_declaration();
+            return;
+        }
+
         let global = self.parse_variable("Expect variable name");
         if self.match_advance(&TokenEqual) {
             self.expression();
@@ -127,6 +210,268 @@ impl<'a> Parser {
         self.define_var(global as u8);
     }
 
+    /// Compiles `fun name(...) {}`, `fun* name` declares a generator. An `async` prefix has already
+    /// been consumed when `is_async` is set.
+    pub fn fun_declaration(&mut self, is_async: bool) {
+        let function_type = match self.match_advance(&TokenStar) {
+            true if is_async => {
+                self.error("A generator can't be async.");
+                FunctionType::TypeAsync
+            }
+            true => FunctionType::TypeGenerator,
+            false if is_async => FunctionType::TypeAsync,
+            false => FunctionType::TypeFunction,
+        };
+        let global = self.parse_variable("Expect function name.");
+        let name = self.prev_tok.as_ref().unwrap().raw.clone();
+
+        self.function(function_type, &name);
+        self.define_var(global as u8);
+    }
+
+    /// Compiles a parameter list and body into a new function and emits the closure creating it.
+    pub(crate) fn function(&mut self, function_type: FunctionType, name: &str) {
+        self.begin_function(function_type);
+        self.scope.begin_scope();
+
+        self.consume(&TokenLeftParen, "Expect '(' after function name.");
+        let params = self.parameters();
+        match function_type {
+            FunctionType::TypeGetter if !params.is_empty() => self.error("A getter can't have parameters."),
+            FunctionType::TypeSetter if params.len() != 1 || params[0].rest || params[0].default.is_some() => {
+                self.error("A setter must have exactly one parameter.")
+            }
+            _ => {}
+        }
+
+        self.consume(&TokenLeftBrace, "Expect '{' before function body.");
+        self.block();
+
+        self.end_function(name, params);
+    }
+
+    /// Declares the parameters of the function being compiled as its first locals, the opening
+    /// `(` is already consumed. `name = value` gives a parameter a default, all parameters after
+    /// it need one too. A last `...name` parameter receives the extra arguments as a list.
+    fn parameters(&mut self) -> Vec<Parameter> {
+        let mut params: Vec<Parameter> = Vec::new();
+        if !self.curr_is(&TokenRightParen) {
+            loop {
+                if params.len() == u8::MAX as usize {
+                    self.error_at_curr("Can't have more than 255 parameters.");
+                }
+                if self.match_advance(&TokenDotDotDot) {
+                    self.consume(&TokenIdentifier, "Expect rest parameter name.");
+                    let name = self.prev_tok.clone().unwrap();
+                    self.add_local(&name);
+                    params.push(Parameter { name: name.raw, default: None, rest: true });
+                    if self.curr_is(&TokenComma) {
+                        self.error_at_curr("The rest parameter must be the last one.");
+                    }
+                    break;
+                }
+                self.consume(&TokenIdentifier, "Expect parameter name.");
+                let name = self.prev_tok.clone().unwrap();
+
+                let default = match self.match_advance(&TokenEqual) {
+                    true => Some(self.default_value(&name.raw)),
+                    false => {
+                        if params.iter().any(|param| param.default.is_some()) {
+                            self.error("Parameters after one with a default need a default too.");
+                        }
+                        None
+                    }
+                };
+                // declared after its default, which only sees the parameters before it
+                self.add_local(&name);
+                params.push(Parameter { name: name.raw, default, rest: false });
+
+                if !self.match_advance(&TokenComma) {
+                    break;
+                }
+            }
+        }
+        self.consume(&TokenRightParen, "Expect ')' after parameters.");
+        self.sync_stack_depth();
+
+        params
+    }
+
+    /// Compiles the default value of parameter `name` into a function of no arguments.
+    fn default_value(&mut self, name: &str) -> DefaultValue {
+        self.begin_function(FunctionType::TypeFunction);
+        self.expression();
+        self.codegen.emit_return();
+
+        let (function, upvalues) = self.finish_function(name, Vec::new());
+        DefaultValue { function: Rc::new(function), upvalues }
+    }
+
+    /// Compiles the anonymous function expression `fun (params) { body }`, `fun*` makes it a generator.
+    pub fn fun_expression(&mut self, _can_assign: bool) {
+        let function_type = match self.match_advance(&TokenStar) {
+            true => FunctionType::TypeGenerator,
+            false => FunctionType::TypeFunction,
+        };
+        self.function(function_type, "lambda");
+    }
+
+    /// Looks ahead from just after an opening `(` for `params) =>`, the start of an arrow lambda.
+    /// Only a parameter list counts, so `x if (x > 1) => ...` in a match arm stays a guard.
+    pub(crate) fn is_arrow_lambda(&self) -> bool {
+        let mut lex = self.lex.clone();
+        let mut tok_type = self.curr_tok_type();
+
+        while tok_type != TokenRightParen {
+            if tok_type == TokenDotDotDot {
+                tok_type = lex.scan_next().token_type;
+            }
+            if tok_type != TokenIdentifier {
+                return false;
+            }
+            tok_type = lex.scan_next().token_type;
+
+            if tok_type == TokenEqual {
+                // skips the default value up to the `,` or `)` closing it
+                let mut depth = 0;
+                loop {
+                    tok_type = lex.scan_next().token_type;
+                    match tok_type {
+                        TokenComma | TokenRightParen | TokenRightBrace | TokenRightBracket if depth == 0 => break,
+                        TokenLeftParen | TokenLeftBrace | TokenLeftBracket => depth += 1,
+                        TokenRightParen | TokenRightBrace | TokenRightBracket => depth -= 1,
+                        TokenEof => return false,
+                        _ => {}
+                    }
+                }
+            }
+
+            match tok_type {
+                TokenComma => tok_type = lex.scan_next().token_type,
+                TokenRightParen => {}
+                _ => return false,
+            }
+        }
+        lex.scan_next().is(TokenEqualGreater)
+    }
+
+    /// Compiles `(params) => expression`, which returns the expression, or `(params) => { body }`.
+    /// The opening `(` is already consumed.
+    fn arrow_lambda(&mut self) {
+        self.begin_function(FunctionType::TypeFunction);
+        self.scope.begin_scope();
+
+        let params = self.parameters();
+        self.consume(&TokenEqualGreater, "Expect '=>' after lambda parameters.");
+
+        if self.match_advance(&TokenLeftBrace) {
+            self.block();
+        } else {
+            self.expression();
+            self.codegen.emit_return();
+        }
+
+        self.end_function("lambda", params);
+    }
+
+    pub(crate) fn begin_function(&mut self, function_type: FunctionType) {
+        let enclosing = Compiler {
+            function_type: std::mem::replace(&mut self.function_type, function_type),
+            scope: std::mem::take(&mut self.scope),
+            codegen: std::mem::take(&mut self.codegen),
+            loops: std::mem::take(&mut self.loops),
+            tries: std::mem::take(&mut self.tries),
+        };
+        self.codegen.line = enclosing.codegen.line;
+        self.enclosing.push(enclosing);
+
+        if let FunctionType::TypeMethod
+        | FunctionType::TypeInitializer
+        | FunctionType::TypeGetter
+        | FunctionType::TypeSetter = function_type
+        {
+            // slot zero holds the receiver
+            self.scope.locals[0] = Local::new("this".to_string(), 0);
+        }
+        self.sync_stack_depth();
+    }
+
+    /// Counts only the locals as on the stack, which is all it holds between statements and when a
+    /// function starts.
+    fn sync_stack_depth(&mut self) {
+        self.codegen.stack_depth = self.scope.locals.len();
+    }
+
+    pub(crate) fn end_function(&mut self, name: &str, params: Vec<Parameter>) {
+        let (function, upvalues) = self.finish_function(name, params);
+        let constant = self.codegen.bytecodes.add_const(Value(ValueRepr::Function(Rc::new(function))));
+        self.codegen.emit_op_operand(OpClosure, constant as u8);
+
+        for upvalue in upvalues {
+            self.codegen.emit_bytes(&[upvalue.is_local as u8, upvalue.index]);
+        }
+    }
+
+    /// Ends the function being compiled and returns it with the variables its closure captures.
+    fn finish_function(&mut self, name: &str, params: Vec<Parameter>) -> (Function, Vec<UpvalueDesc>) {
+        self.emit_return();
+
+        let enclosing = self.enclosing.pop().unwrap();
+        let codegen = std::mem::replace(&mut self.codegen, enclosing.codegen);
+        self.codegen.line = codegen.line;
+        let scope = std::mem::replace(&mut self.scope, enclosing.scope);
+        let function_type = std::mem::replace(&mut self.function_type, enclosing.function_type);
+        self.loops = enclosing.loops;
+        self.tries = enclosing.tries;
+
+        let mut function = Function::new(name, params.len() as u8, scope.upvalues.len() as u8, codegen.bytecodes);
+        function.params = params;
+        function.is_generator = function_type == FunctionType::TypeGenerator;
+        function.is_async = function_type == FunctionType::TypeAsync;
+
+        (function, scope.upvalues)
+    }
+
+    pub fn return_statement(&mut self) {
+        if self.function_type == FunctionType::TypeScript {
+            self.error("Can't return from top-level code.");
+        }
+
+        if self.match_advance(&TokenSemicolon) {
+            self.close_loop_iterators();
+            self.emit_return();
+        } else {
+            if self.function_type == FunctionType::TypeInitializer {
+                self.error("Can't return a value from an initializer.");
+            }
+            if self.function_type == FunctionType::TypeSetter {
+                self.error("Can't return a value from a setter.");
+            }
+            self.expression();
+            self.consume(&TokenSemicolon, "Expect ';' after return value.");
+
+            // a frame with a try block open, an iterator to close, or a generator or task behind it,
+            // has to stay around
+            let closed_iterators = self.close_loop_iterators();
+            let plain_function = matches!(self.function_type, FunctionType::TypeFunction | FunctionType::TypeMethod);
+            if self.tries == 0 && plain_function && !closed_iterators {
+                self.codegen.make_tail_call();
+            }
+            self.codegen.emit_return();
+        }
+    }
+
+    /// Closes the iterators of the `for-in` loops a `return` leaves, innermost first. Whether there
+    /// were any.
+    fn close_loop_iterators(&mut self) -> bool {
+        let iterators: Vec<u8> = self.loops.iter().rev().filter_map(|ctx| ctx.iterator).collect();
+        for slot in iterators.iter() {
+            self.codegen.emit_op_operand(OpGetLocal, *slot);
+            self.codegen.emit_op(OpIterClose);
+        }
+        !iterators.is_empty()
+    }
+
     pub fn expression_statement(&mut self) {
         self.expression();
         self.consume(&TokenSemicolon, "Expect ';' after value.");
@@ -164,6 +509,11 @@ impl<'a> Parser {
         self.scope.begin_scope();
         self.consume(&TokenLeftParen, "Expect '(' after 'for'.");
 
+        if self.curr_is(&TokenIdentifier) && self.peek_next_type().is(&TokenIn) {
+            self.for_in_statement();
+            return;
+        }
+
         if self.match_advance(&TokenSemicolon) {
             // No initializer
         } else if self.match_advance(&TokenVar) {
@@ -195,6 +545,7 @@ impl<'a> Parser {
             self.codegen.patch_jump(body_jump);
         }
 
+        self.begin_loop(loop_start);
         self.statement();
         self.codegen.emit_loop(loop_start);
 
@@ -202,8 +553,40 @@ impl<'a> Parser {
             self.codegen.patch_jump(i);
             self.codegen.emit_op(OpPop);
         }
+        self.end_loop();
 
-        self.scope.end_scope();
+        self.codegen.emit_op_operand(OpPopN, self.scope.end_scope());
+    }
+
+    /// Compiles `for (x in iterable) body`. The iterator lives in a hidden local for the whole loop
+    /// and the loop variable is declared in a fresh scope on every iteration.
+    fn for_in_statement(&mut self) {
+        self.advance();
+        let name = self.prev_tok.clone().unwrap();
+        self.consume(&TokenIn, "Expect 'in' after loop variable.");
+
+        self.expression();
+        self.consume(&TokenRightParen, "Expect ')' after for clauses.");
+
+        self.codegen.emit_op(OpIterInit);
+        self.scope.add_local(&"for iterator".to_string());
+        let iterator = (self.scope.locals.len() - 1) as u8;
+
+        let loop_start = self.codegen.bytecodes.code_count;
+        let exit_jump = self.codegen.emit_jump(OpIterNext);
+
+        self.begin_loop(loop_start);
+        self.loops.last_mut().unwrap().iterator = Some(iterator);
+        self.scope.begin_scope();
+        self.add_local(&name);
+        self.statement();
+        self.codegen.emit_op_operand(OpPopN, self.scope.end_scope());
+
+        self.codegen.emit_loop(loop_start);
+        self.codegen.patch_jump(exit_jump);
+        self.end_loop();
+
+        self.codegen.emit_op_operand(OpPopN, self.scope.end_scope());
     }
 
     pub fn while_statement(&mut self) {
@@ -215,23 +598,243 @@ impl<'a> Parser {
         let exit_jump = self.codegen.emit_jump(OpJumpIfFalse);
         self.codegen.emit_op(OpPop);
 
+        self.begin_loop(loop_start);
         self.statement();
 
         self.codegen.emit_loop(loop_start);
 
         self.codegen.patch_jump(exit_jump);
         self.codegen.emit_op(OpPop);
+        self.end_loop();
+    }
+
+    fn begin_loop(&mut self, start: usize) {
+        let label = self.pending_label.take();
+        let depth = self.scope.scope_depth;
+        self.loops.push(LoopContext { label, start, depth, tries: self.tries, breaks: Vec::new(), iterator: None });
+    }
+
+    fn end_loop(&mut self) {
+        let ctx = self.loops.pop().unwrap();
+        for jump in ctx.breaks {
+            self.codegen.patch_jump(jump);
+        }
+    }
+
+    /// Parses the optional label after `break` or `continue` and finds the loop it refers to.
+    fn loop_target(&mut self, keyword: &str) -> Option<usize> {
+        let label = match self.match_advance(&TokenIdentifier) {
+            true => Some(self.prev_tok.as_ref().unwrap().raw.clone()),
+            false => None,
+        };
+        self.consume(&TokenSemicolon, &format!("Expect ';' after '{}'.", keyword));
+
+        if self.loops.is_empty() {
+            self.error(&format!("Can't use '{}' outside of a loop.", keyword));
+            return None;
+        }
+
+        match label {
+            None => Some(self.loops.len() - 1),
+            Some(label) => {
+                let target = self.loops.iter().rposition(|ctx| ctx.label.as_ref() == Some(&label));
+                if target.is_none() {
+                    self.error(&format!("No enclosing loop labeled '{}'.", label));
+                }
+                target
+            }
+        }
+    }
+
+    /// Pops the locals and try blocks entered inside the loops up to `target` before jumping out
+    /// of them, innermost first. The iterators of the `for-in` loops left are closed on the way,
+    /// `target` itself is only left by `break`.
+    fn exit_loop_scopes(&mut self, target: usize, leave_target: bool) {
+        let (mut popped, mut tries) = (0, self.tries);
+        for index in (target..self.loops.len()).rev() {
+            let pops = self.scope.count_deeper_than(self.loops[index].depth) - popped;
+            let left = tries - self.loops[index].tries;
+            if left > 0 {
+                // the VM runs the finally blocks of the left try statements before carrying on
+                self.codegen.emit_op_operand2(OpLeave, pops, left as u8);
+            } else if pops > 0 {
+                self.codegen.emit_op_operand(OpPopN, pops);
+            }
+            (popped, tries) = (popped + pops, self.loops[index].tries);
+
+            match self.loops[index].iterator {
+                Some(slot) if index > target || leave_target => {
+                    self.codegen.emit_op_operand(OpGetLocal, slot);
+                    self.codegen.emit_op(OpIterClose);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn break_statement(&mut self) {
+        if let Some(target) = self.loop_target("break") {
+            self.exit_loop_scopes(target, true);
+            let jump = self.codegen.emit_jump(OpJump);
+            self.loops[target].breaks.push(jump);
+        }
+    }
+
+    pub fn continue_statement(&mut self) {
+        if let Some(target) = self.loop_target("continue") {
+            self.exit_loop_scopes(target, false);
+            self.codegen.emit_loop(self.loops[target].start);
+        }
+    }
+
+    /// Compiles `yield value;`, handing the value to the caller and suspending the generator.
+    pub fn yield_statement(&mut self) {
+        if self.function_type != FunctionType::TypeGenerator {
+            self.error("Can't use 'yield' outside of a generator.");
+        }
+
+        if self.match_advance(&TokenSemicolon) {
+            self.codegen.emit_op(OpNil);
+        } else {
+            self.expression();
+            self.consume(&TokenSemicolon, "Expect ';' after yielded value.");
+        }
+        self.codegen.emit_op(OpYield);
+    }
+
+    pub fn throw_statement(&mut self) {
+        self.expression();
+        self.consume(&TokenSemicolon, "Expect ';' after thrown value.");
+        self.codegen.emit_op(OpThrow);
+    }
+
+    /// Compiles `try { } catch (e) { } finally { }`, either clause may be left out but not both.
+    /// The finally block runs once the try or catch block completes, throws or returns, with a
+    /// hidden value and completion kind pair telling `OpEndFinally` how to carry on afterwards.
+    pub fn try_statement(&mut self) {
+        let (handler, try_finally) = self.codegen.emit_try(0);
+        self.protected_block("Expect '{' after 'try'.");
+        let mut completed_jumps = vec![self.codegen.emit_jump(OpJump)];
+        let mut finally_jumps = vec![try_finally];
+
+        // the thrown value is on top of the stack from here on
+        self.codegen.patch_jump(handler);
+        let has_catch = self.match_advance(&TokenCatch);
+        if has_catch {
+            self.scope.begin_scope();
+            let name = match self.match_advance(&TokenLeftParen) {
+                true => {
+                    self.consume(&TokenIdentifier, "Expect exception variable name.");
+                    let name = self.prev_tok.clone().unwrap();
+                    self.consume(&TokenRightParen, "Expect ')' after exception variable.");
+                    name.raw
+                }
+                false => "catch exception".to_string(),
+            };
+            self.scope.add_local(&name);
+
+            // the caught value is unwound too, a value thrown by the catch block replaces it
+            let (rethrow, catch_finally) = self.codegen.emit_try(1);
+            finally_jumps.push(catch_finally);
+            self.protected_block("Expect '{' after catch clause.");
+            self.codegen.emit_op_operand(OpPopN, self.scope.end_scope());
+            completed_jumps.push(self.codegen.emit_jump(OpJump));
+
+            self.codegen.patch_jump(rethrow);
+        }
+
+        if !self.match_advance(&TokenFinally) {
+            if !has_catch {
+                self.error_at_curr("Expect 'catch' or 'finally' after try block.");
+            }
+            self.codegen.emit_op(OpThrow);
+            for jump in completed_jumps {
+                self.codegen.patch_jump(jump);
+            }
+            return;
+        }
+
+        self.codegen.emit_const_i64(FINALLY_THROW);
+        let thrown_jump = self.codegen.emit_jump(OpJump);
+        for jump in completed_jumps {
+            self.codegen.patch_jump(jump);
+        }
+        self.codegen.emit_op(OpNil);
+        self.codegen.emit_const_i64(FINALLY_NORMAL);
+
+        self.codegen.patch_jump(thrown_jump);
+        for jump in finally_jumps {
+            self.codegen.patch_jump(jump);
+        }
+
+        self.scope.begin_scope();
+        self.scope.add_local(&"finally value".to_string());
+        self.scope.add_local(&"finally completion".to_string());
+
+        self.consume(&TokenLeftBrace, "Expect '{' after 'finally'.");
+        self.scope.begin_scope();
+        self.block();
+        self.codegen.emit_op_operand(OpPopN, self.scope.end_scope());
+
+        // OpEndFinally consumes the value and completion kind
+        self.scope.end_scope();
+        self.codegen.emit_op(OpEndFinally);
+    }
+
+    /// Compiles a block guarded by the handler the caller just pushed and pops it on completion.
+    fn protected_block(&mut self, msg: &str) {
+        self.consume(&TokenLeftBrace, msg);
+        self.tries += 1;
+        self.scope.begin_scope();
+        self.block();
+        self.codegen.emit_op_operand(OpPopN, self.scope.end_scope());
+        self.tries -= 1;
+        self.codegen.emit_op(OpPopTry);
+    }
+
+    /// Compiles `label: loop`, only `while` and `for` loops can be labeled.
+    fn labeled_statement(&mut self) {
+        self.advance();
+        self.pending_label = Some(self.prev_tok.as_ref().unwrap().raw.clone());
+        self.consume(&TokenColon, "Expect ':' after label.");
+
+        if self.match_advance(&TokenWhile) {
+            self.while_statement();
+        } else if self.match_advance(&TokenFor) {
+            self.for_statement();
+        } else {
+            self.error_at_curr("Labels can only be applied to loops.");
+            self.pending_label = None;
+        }
     }
 
     pub fn declaration(&mut self) {
+        self.sync_stack_depth();
         if self.match_advance(&TokenVar) {
             self.var_declaration();
+        } else if self.match_advance(&TokenFun) {
+            self.fun_declaration(false);
+        } else if self.match_advance(&TokenAsync) {
+            self.consume(&TokenFun, "Expect 'fun' after 'async'.");
+            self.fun_declaration(true);
+        } else if self.match_advance(&TokenClass) {
+            self.class_declaration();
+        } else if self.match_advance(&TokenTrait) {
+            self.trait_declaration();
+        } else if self.match_advance(&TokenEnum) {
+            self.enum_declaration();
+        } else if self.match_advance(&TokenImport) {
+            self.import_declaration();
+        } else if self.is_from_import() {
+            self.advance();
+            self.from_import_declaration();
         } else {
             self.statement();
         }
     }
 
     pub fn statement(&mut self) {
+        self.sync_stack_depth();
         if self.match_advance(&TokenPrint) {
             self.print_statement();
         } else if self.match_advance(&TokenIf) {
@@ -240,6 +843,20 @@ impl<'a> Parser {
             self.while_statement();
         } else if self.match_advance(&TokenFor) {
             self.for_statement();
+        } else if self.match_advance(&TokenReturn) {
+            self.return_statement();
+        } else if self.match_advance(&TokenYield) {
+            self.yield_statement();
+        } else if self.match_advance(&TokenThrow) {
+            self.throw_statement();
+        } else if self.match_advance(&TokenTry) {
+            self.try_statement();
+        } else if self.match_advance(&TokenBreak) {
+            self.break_statement();
+        } else if self.match_advance(&TokenContinue) {
+            self.continue_statement();
+        } else if self.curr_is(&TokenIdentifier) && self.peek_next_type().is(&TokenColon) {
+            self.labeled_statement();
         } else if self.match_advance(&TokenLeftBrace) {
             self.scope.begin_scope();
             self.block();
@@ -249,17 +866,25 @@ impl<'a> Parser {
         }
     }
 
-    pub fn number(&mut self) {
-        let value = self.prev_tok.as_ref();
-        let value: f64 = match value {
-            None => 0.0,
-            Some(val) => val.raw.parse().unwrap_or(0.0),
+    pub fn number(&mut self, _can_assign: bool) {
+        let raw = match self.prev_tok.as_ref() {
+            None => "0".to_string(),
+            Some(val) => val.raw.clone(),
         };
 
-        self.codegen.emit_const_f64(value);
+        match parse_number(&raw) {
+            Ok(ValueRepr::Integer(value)) => {
+                self.codegen.emit_const_i64(value);
+            }
+            Ok(ValueRepr::Number(value)) => {
+                self.codegen.emit_const_f64(value);
+            }
+            Ok(_) => unreachable!(),
+            Err(msg) => self.error(&msg),
+        }
     }
 
-    pub fn string(&mut self) {
+    pub fn string(&mut self, _can_assign: bool) {
         let value = self.prev_tok.as_ref();
         let value = match value {
             None => "",
@@ -269,30 +894,229 @@ impl<'a> Parser {
         self.codegen.emit_const_string(value.to_owned());
     }
 
-    pub fn named_variable(&mut self) {
-        let name = &self.prev_tok.as_ref().unwrap().raw;
+    /// Finds the get and set instructions, and their operand, for the variable called `name`.
+    pub(crate) fn resolve_variable(&mut self, name: &str) -> (OpCode, OpCode, u8) {
+        if let Some(v) = self.scope.resolve_local(name) {
+            return (OpGetLocal, OpSetLocal, v);
+        }
+
+        match self.resolve_upvalue(name) {
+            Some(v) => (OpGetUpvalue, OpSetUpvalue, v),
+            None => (OpGetGlobal, OpSetGlobal, self.name_const(name) as u8),
+        }
+    }
+
+    /// Resolves `name` as a local of one of the enclosing functions and threads it as an upvalue
+    /// through every function between that one and the current one.
+    fn resolve_upvalue(&mut self, name: &str) -> Option<u8> {
+        let (level, slot) = (0..self.enclosing.len())
+            .rev()
+            .find_map(|level| self.enclosing[level].scope.resolve_local(name).map(|slot| (level, slot)))?;
+
+        let mut index = slot;
+        let mut is_local = true;
+        for compiler in self.enclosing.iter_mut().skip(level + 1) {
+            index = compiler.scope.add_upvalue(index, is_local) as u8;
+            is_local = false;
+        }
+
+        let upvalue = self.scope.add_upvalue(index, is_local);
+        if upvalue > u8::MAX as usize {
+            self.error("Too many closure variables in function.");
+        }
+        Some(upvalue as u8)
+    }
+
+    pub fn named_variable(&mut self, can_assign: bool) {
+        let name = self.prev_tok.as_ref().unwrap().raw.clone();
+
+        let (get_op, set_op, arg) = self.resolve_variable(&name);
 
-        let (get_op, set_op, arg) = match self.scope.resolve_local(name) {
-            None => (OpGetGlobal, OpSetGlobal, self.ident_const() as u8),
-            Some(v) => (OpGetLocal, OpSetLocal, v),
+        if can_assign && self.match_advance(&TokenEqual) {
+            self.expression();
+            self.codegen.emit_op_operand(set_op, arg);
+        } else if let Some(op) = self.compound_assignment(can_assign) {
+            self.codegen.emit_op_operand(get_op, arg);
+            self.expression();
+            self.codegen.emit_op(op);
+            self.codegen.emit_op_operand(set_op, arg);
+        } else {
+            self.codegen.emit_op_operand(get_op, arg);
+        }
+    }
+
+    /// Consumes a compound assignment operator such as `+=`, returning the arithmetic it applies.
+    pub(crate) fn compound_assignment(&mut self, can_assign: bool) -> Option<OpCode> {
+        if !can_assign {
+            return None;
+        }
+
+        let op = match self.curr_tok_type() {
+            TokenPlusEqual => OpAdd,
+            TokenMinusEqual => OpSubtract,
+            TokenStarEqual => OpMultiple,
+            TokenSlashEqual => OpDivide,
+            TokenPercentEqual => OpModulo,
+            _ => return None,
         };
+        self.advance();
+        Some(op)
+    }
 
-        match self.match_advance(&TokenEqual) {
-            true => {
-                self.expression();
-                self.codegen.emit_op_operand(set_op, arg);
+    pub fn variable(&mut self, can_assign: bool) {
+        self.named_variable(can_assign)
+    }
+
+    pub fn list(&mut self, _can_assign: bool) {
+        let mut count: u8 = 0;
+        while !self.curr_is(&TokenRightBracket) && !self.curr_is(&TokenEof) {
+            self.expression();
+            if count == u8::MAX {
+                self.error("Can't have more than 255 items in a list literal.");
+            }
+            count = count.wrapping_add(1);
+
+            if !self.match_advance(&TokenComma) {
+                break;
+            }
+        }
+
+        self.consume(&TokenRightBracket, "Expect ']' after list items.");
+        self.codegen.emit_op_operand(OpBuildList, count);
+    }
+
+    pub fn map(&mut self, _can_assign: bool) {
+        let mut count: u8 = 0;
+        while !self.curr_is(&TokenRightBrace) && !self.curr_is(&TokenEof) {
+            self.expression();
+            self.consume(&TokenColon, "Expect ':' after map key.");
+            self.expression();
+            if count == u8::MAX {
+                self.error("Can't have more than 255 entries in a map literal.");
             }
-            false => {
-                self.codegen.emit_op_operand(get_op, arg);
+            count = count.wrapping_add(1);
+
+            if !self.match_advance(&TokenComma) {
+                break;
             }
         }
+
+        self.consume(&TokenRightBrace, "Expect '}' after map entries.");
+        self.codegen.emit_op_operand(OpBuildMap, count);
+    }
+
+    pub fn index(&mut self, can_assign: bool) {
+        self.expression();
+        self.consume(&TokenRightBracket, "Expect ']' after index.");
+
+        if can_assign && self.match_advance(&TokenEqual) {
+            self.expression();
+            self.codegen.emit_op(OpIndexSet);
+        } else if let Some(op) = self.compound_assignment(can_assign) {
+            self.codegen.emit_op(OpDup2);
+            self.codegen.emit_op(OpIndexGet);
+            self.expression();
+            self.codegen.emit_op(op);
+            self.codegen.emit_op(OpIndexSet);
+        } else {
+            self.codegen.emit_op(OpIndexGet);
+        }
+    }
+
+    /// Compiles `start..end` and `start..=end`, optionally followed by `step n`.
+    pub fn range(&mut self, _can_assign: bool) {
+        let inclusive = self.prev_tok_type().is(&TokenDotDotEqual);
+        let operand_precedence = PrecedenceRange.add(1);
+
+        self.parse(&operand_precedence);
+
+        // `step` is only a keyword in this position
+        let has_step = self.curr_is(&TokenIdentifier) && self.curr_tok.as_ref().unwrap().raw == "step";
+        if has_step {
+            self.advance();
+            self.parse(&operand_precedence);
+        }
+
+        self.codegen.emit_op_operand(OpRange, (inclusive as u8) | (has_step as u8) << 1);
+    }
+
+    pub fn call(&mut self, _can_assign: bool) {
+        let (argc, names, spread) = self.argument_list();
+        if spread {
+            self.codegen.emit_op(OpCallSpread);
+            return;
+        }
+        if names.is_empty() {
+            self.codegen.emit_call(argc);
+            return;
+        }
+
+        let names = names.into_iter().map(|name| Value(ValueRepr::String(name))).collect();
+        let constant = self.codegen.bytecodes.add_const(Value::tuple(names));
+        self.codegen.emit_op_operand2(OpCallNamed, argc, constant as u8);
     }
 
-    pub fn variable(&mut self) {
-        self.named_variable()
+    /// Compiles call arguments, `name: value` passes a parameter by name after the positional
+    /// ones. Returns the argument count, the names of the trailing named arguments and whether
+    /// `...value` spreads an argument. From the first spread on, the arguments are collected in a
+    /// list instead, the ones already pushed included, and the count is only known at runtime.
+    fn argument_list(&mut self) -> (u8, Vec<String>, bool) {
+        let mut argc: u8 = 0;
+        let mut names: Vec<String> = Vec::new();
+        let mut spread = false;
+        if !self.curr_is(&TokenRightParen) {
+            loop {
+                if self.match_advance(&TokenDotDotDot) {
+                    if !names.is_empty() {
+                        self.error("Can't spread arguments after named arguments.");
+                    }
+                    if !spread {
+                        self.codegen.emit_op_operand(OpBuildList, argc);
+                        spread = true;
+                    }
+                    self.expression();
+                    self.codegen.emit_op(OpSpread);
+
+                    if !self.match_advance(&TokenComma) {
+                        break;
+                    }
+                    continue;
+                }
+
+                if spread && self.curr_is(&TokenIdentifier) && self.peek_next_type().is(&TokenColon) {
+                    self.error_at_curr("Can't pass named arguments after spread arguments.");
+                } else if self.curr_is(&TokenIdentifier) && self.peek_next_type().is(&TokenColon) {
+                    self.advance();
+                    let name = self.prev_tok.as_ref().unwrap().raw.clone();
+                    if names.contains(&name) {
+                        self.error(&format!("Duplicate argument '{}'.", name));
+                    }
+                    names.push(name);
+                    self.advance();
+                } else if !names.is_empty() {
+                    self.error_at_curr("Positional arguments can't follow named arguments.");
+                }
+
+                self.expression();
+                if spread {
+                    self.codegen.emit_op_operand(OpBuildList, 1);
+                    self.codegen.emit_op(OpSpread);
+                } else if argc == u8::MAX {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                argc = argc.wrapping_add(1);
+
+                if !self.match_advance(&TokenComma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(&TokenRightParen, "Expect ')' after arguments.");
+        (argc, names, spread)
     }
 
-    pub fn literal(&mut self) {
+    pub fn literal(&mut self, _can_assign: bool) {
         let prev_tok_type = self.prev_tok_type();
         match prev_tok_type {
             TokenFalse => self.codegen.emit_op(OpFalse),
@@ -303,31 +1127,72 @@ impl<'a> Parser {
         };
     }
 
-    pub fn unary(&mut self) {
+    pub fn unary(&mut self, _can_assign: bool) {
         let prev_tok_type = self.prev_tok_type();
+        if prev_tok_type == TokenMinus && self.is_min_integer() {
+            self.advance();
+            self.codegen.emit_const_i64(i64::MIN);
+            return;
+        }
 
         self.parse(&PrecedenceUnary);
 
         match prev_tok_type {
             TokenMinus => self.codegen.emit_op(OpNegate),
             TokenBang => self.codegen.emit_op(OpNot),
+            TokenTilde => self.codegen.emit_op(OpBitNot),
             _ => return,
         };
     }
 
-    pub fn binary(&mut self) {
+    /// Whether the current token is the literal `9223372036854775808` after a `-`. Only the minus
+    /// brings it in range, so the two are folded into one integer literal.
+    fn is_min_integer(&self) -> bool {
+        match self.curr_tok.as_ref() {
+            Some(tok) if tok.is(TokenNumber) => {
+                parse_number(&tok.raw).is_err()
+                    && matches!(parse_negated_number(&tok.raw), Ok(ValueRepr::Integer(i64::MIN)))
+            }
+            _ => false,
+        }
+    }
+
+    /// Compiles `await operand`, suspending until the task it evaluates to completes. Top-level code
+    /// runs the scheduler until then instead of suspending.
+    pub fn await_(&mut self, _can_assign: bool) {
+        if !matches!(self.function_type, FunctionType::TypeAsync | FunctionType::TypeScript) {
+            self.error("Can't use 'await' outside of an async function.");
+        }
+
+        self.parse(&PrecedenceUnary);
+        self.codegen.emit_op(OpAwait);
+    }
+
+    pub fn binary(&mut self, _can_assign: bool) {
         let prev_tok_type = self.prev_tok_type();
         let rule = self.get_rule(&prev_tok_type);
 
-        let precedence = &rule.precedence.add(1);
+        // `**` is right-associative, its right operand may contain another `**`
+        let precedence = match prev_tok_type {
+            TokenStarStar => rule.precedence.clone(),
+            _ => rule.precedence.add(1),
+        };
 
-        self.parse(precedence);
+        self.parse(&precedence);
 
         match prev_tok_type {
             TokenPlus => self.codegen.emit_op(OpAdd),
             TokenMinus => self.codegen.emit_op(OpSubtract),
             TokenStar => self.codegen.emit_op(OpMultiple),
             TokenSlash => self.codegen.emit_op(OpDivide),
+            TokenDiv => self.codegen.emit_op(OpFloorDivide),
+            TokenPercent => self.codegen.emit_op(OpModulo),
+            TokenStarStar => self.codegen.emit_op(OpPower),
+            TokenAmpersand => self.codegen.emit_op(OpBitAnd),
+            TokenPipe => self.codegen.emit_op(OpBitOr),
+            TokenCaret => self.codegen.emit_op(OpBitXor),
+            TokenLessLess => self.codegen.emit_op(OpShiftLeft),
+            TokenGreaterGreater => self.codegen.emit_op(OpShiftRight),
             TokenBangEqual => self.codegen.emit_op2(OpEqual, OpNot),
             TokenEqualEqual => self.codegen.emit_op(OpEqual),
             TokenGreater => self.codegen.emit_op(OpGreater),
@@ -363,7 +1228,13 @@ impl<'a> Parser {
         self.had_error = true
     }
 
+    pub fn warning_at(&mut self, token: &Option<Token>, msg: &str) {
+        let tok = token.as_ref().unwrap();
+        eprintln!("[line {}] Warning at {}: {}", tok.line, tok.raw, msg);
+    }
+
     pub fn parse(&mut self, precedence: &ParsePrecedence) {
+        let enclosing_chain = std::mem::take(&mut self.optional_chain);
         self.advance();
 
         let tok_type: &TokenType = &self.prev_tok_type();
@@ -371,9 +1242,11 @@ impl<'a> Parser {
 
         let prefix_rule = rule.prefix;
 
+        // only an operand parsed at assignment precedence may be followed by `=`
+        let can_assign = *precedence <= PrecedenceAssignment;
         match prefix_rule {
             None => self.error("Expected expression"),
-            Some(p) => p(self),
+            Some(p) => p(self, can_assign),
         }
 
         loop {
@@ -385,6 +1258,10 @@ impl<'a> Parser {
             if precedence > curr_precedence {
                 break;
             }
+            // a `?.` chain only spans the calls, indexes and property accesses that follow it
+            if *curr_precedence != PrecedenceCall {
+                self.end_optional_chain();
+            }
 
             self.advance();
 
@@ -394,9 +1271,16 @@ impl<'a> Parser {
 
             match infix_rule {
                 None => self.error("Expected expression"),
-                Some(i) => i(self),
+                Some(i) => i(self, can_assign),
             }
         }
+
+        if can_assign && (self.match_advance(&TokenEqual) || self.compound_assignment(true).is_some()) {
+            self.error("Invalid assignment target.");
+        }
+
+        self.end_optional_chain();
+        self.optional_chain = enclosing_chain;
     }
 
     pub fn parse_variable(&mut self, msg: &str) -> usize {
@@ -413,15 +1297,19 @@ impl<'a> Parser {
 
     pub fn ident_const(&mut self) -> usize {
         let value = match self.prev_tok.as_ref() {
-            None => "",
-            Some(val) => &val.raw,
+            None => String::new(),
+            Some(val) => val.raw.clone(),
         };
 
-        self.codegen.emit_const_string(value.to_owned())
+        self.name_const(&value)
+    }
+
+    pub fn name_const(&mut self, name: &str) -> usize {
+        self.codegen.bytecodes.add_const(Value(ValueRepr::String(name.to_owned())))
     }
 
     pub fn add_local(&mut self, tok: &Token) {
-        if self.scope.local_count >= i8::MAX {
+        if self.scope.local_count == i8::MAX {
             // TODO: error too many variables in function
             return;
         }
@@ -449,11 +1337,11 @@ impl<'a> Parser {
             return;
         }
 
-        self.codegen.emit_bytes(&[OpDefineGlobal.into(), global]);
+        self.codegen.emit_op_operand(OpDefineGlobal, global);
     }
 
-    pub fn and_(&mut self) {
-        let end_jump = self.codegen.emit_op(OpJumpIfFalse);
+    pub fn and_(&mut self, _can_assign: bool) {
+        let end_jump = self.codegen.emit_jump(OpJumpIfFalse);
         self.codegen.emit_op(OpPop);
 
         self.parse(&PrecedenceAnd);
@@ -461,9 +1349,58 @@ impl<'a> Parser {
         self.codegen.patch_jump(end_jump);
     }
 
-    pub fn or_(&mut self) {
-        let else_jump = self.codegen.emit_op(OpJumpIfFalse);
-        let end_jump = self.codegen.emit_op(OpJump);
+    /// Compiles `cond ? then : else`.
+    pub fn conditional(&mut self, _can_assign: bool) {
+        let else_jump = self.codegen.emit_jump(OpJumpIfFalse);
+        self.codegen.emit_op(OpPop);
+        self.parse(&PrecedenceConditional);
+        let end_jump = self.codegen.emit_jump(OpJump);
+
+        self.consume(&TokenColon, "Expect ':' after then branch of conditional expression.");
+        self.codegen.patch_jump(else_jump);
+        self.codegen.emit_op(OpPop);
+        self.parse(&PrecedenceConditional);
+
+        self.codegen.patch_jump(end_jump);
+    }
+
+    /// Compiles `value ?? fallback`, the fallback is only evaluated when the value is nil.
+    pub fn coalesce(&mut self, _can_assign: bool) {
+        let else_jump = self.codegen.emit_jump(OpJumpIfNil);
+        let end_jump = self.codegen.emit_jump(OpJump);
+
+        self.codegen.patch_jump(else_jump);
+        self.codegen.emit_op(OpPop);
+
+        self.parse(&PrecedenceCoalesce);
+
+        self.codegen.patch_jump(end_jump);
+    }
+
+    /// Compiles `object?.name` and `callee?.(args)`, a nil receiver skips the rest of the chain.
+    pub fn optional_chain(&mut self, _can_assign: bool) {
+        let nil_jump = self.codegen.emit_jump(OpJumpIfNil);
+        self.optional_chain.push(nil_jump);
+
+        if self.match_advance(&TokenLeftParen) {
+            self.call(false);
+            return;
+        }
+
+        self.consume(&TokenIdentifier, "Expect property name after '?.'.");
+        let name = self.ident_const();
+        self.codegen.emit_op_operand(OpGetProperty, name as u8);
+    }
+
+    fn end_optional_chain(&mut self) {
+        for jump in std::mem::take(&mut self.optional_chain) {
+            self.codegen.patch_jump(jump);
+        }
+    }
+
+    pub fn or_(&mut self, _can_assign: bool) {
+        let else_jump = self.codegen.emit_jump(OpJumpIfFalse);
+        let end_jump = self.codegen.emit_jump(OpJump);
 
         self.codegen.patch_jump(else_jump);
         self.codegen.emit_op(OpPop);
@@ -6,8 +6,8 @@ use crate::token::TokenType::*;
 
 pub struct ParseRule {
     pub precedence: ParsePrecedence,
-    pub prefix: Option<fn(&mut Parser)>,
-    pub infix: Option<fn(&mut Parser)>,
+    pub prefix: Option<fn(&mut Parser, bool)>,
+    pub infix: Option<fn(&mut Parser, bool)>,
 }
 
 #[derive(Clone, Eq, PartialEq, PartialOrd, Hash, Debug)]
@@ -15,24 +15,40 @@ pub enum ParsePrecedence {
     PrecedenceNone = 1,
     PrecedenceAssignment = 2,
     /* = */
-    PrecedenceOr = 3,
+    PrecedenceConditional = 3,
+    /* ?: */
+    PrecedenceCoalesce = 4,
+    /* ?? */
+    PrecedenceOr = 5,
     /* or */
-    PrecedenceAnd = 4,
+    PrecedenceAnd = 6,
     /* and */
-    PrecedenceEquality = 5,
+    PrecedenceEquality = 7,
     /* ==, != */
-    PrecedenceComparison = 6,
+    PrecedenceComparison = 8,
     /* <, >, <=, >= */
-    PrecedenceTerm = 7,
+    PrecedenceRange = 9,
+    /* .., ..= */
+    PrecedenceBitOr = 10,
+    /* | */
+    PrecedenceBitXor = 11,
+    /* ^ */
+    PrecedenceBitAnd = 12,
+    /* & */
+    PrecedenceShift = 13,
+    /* <<, >> */
+    PrecedenceTerm = 14,
     /* +, - */
-    PrecedenceFactor = 8,
-    /* *, / */
-    PrecedenceUnary = 9,
-    /* !, - */
-    PrecedenceCall = 10,
-    /* ., () */
+    PrecedenceFactor = 15,
+    /* *, /, //, % */
+    PrecedenceUnary = 16,
+    /* !, -, ~ */
+    PrecedenceExponent = 17,
+    /* ** */
+    PrecedenceCall = 18,
+    /* ., ?., (), [] */
 
-    PrecedencePrimary = 11,
+    PrecedencePrimary = 19,
 }
 
 impl ParsePrecedence {
@@ -40,15 +56,23 @@ impl ParsePrecedence {
         match value {
             1 => ParsePrecedence::PrecedenceNone,
             2 => ParsePrecedence::PrecedenceAssignment,
-            3 => ParsePrecedence::PrecedenceOr,
-            4 => ParsePrecedence::PrecedenceAnd,
-            5 => ParsePrecedence::PrecedenceEquality,
-            6 => ParsePrecedence::PrecedenceComparison,
-            7 => ParsePrecedence::PrecedenceTerm,
-            8 => ParsePrecedence::PrecedenceFactor,
-            9 => ParsePrecedence::PrecedenceUnary,
-            10 => ParsePrecedence::PrecedenceCall,
-            11 => ParsePrecedence::PrecedencePrimary,
+            3 => ParsePrecedence::PrecedenceConditional,
+            4 => ParsePrecedence::PrecedenceCoalesce,
+            5 => ParsePrecedence::PrecedenceOr,
+            6 => ParsePrecedence::PrecedenceAnd,
+            7 => ParsePrecedence::PrecedenceEquality,
+            8 => ParsePrecedence::PrecedenceComparison,
+            9 => ParsePrecedence::PrecedenceRange,
+            10 => ParsePrecedence::PrecedenceBitOr,
+            11 => ParsePrecedence::PrecedenceBitXor,
+            12 => ParsePrecedence::PrecedenceBitAnd,
+            13 => ParsePrecedence::PrecedenceShift,
+            14 => ParsePrecedence::PrecedenceTerm,
+            15 => ParsePrecedence::PrecedenceFactor,
+            16 => ParsePrecedence::PrecedenceUnary,
+            17 => ParsePrecedence::PrecedenceExponent,
+            18 => ParsePrecedence::PrecedenceCall,
+            19 => ParsePrecedence::PrecedencePrimary,
             _ => unreachable!(),
         }
     }
@@ -57,16 +81,23 @@ impl ParsePrecedence {
         match self {
             ParsePrecedence::PrecedenceNone => 1,
             ParsePrecedence::PrecedenceAssignment => 2,
-            ParsePrecedence::PrecedenceOr => 3,
-            ParsePrecedence::PrecedenceAnd => 4,
-            ParsePrecedence::PrecedenceEquality => 5,
-            ParsePrecedence::PrecedenceComparison => 6,
-            ParsePrecedence::PrecedenceTerm => 7,
-            ParsePrecedence::PrecedenceFactor => 8,
-            ParsePrecedence::PrecedenceUnary => 9,
-            ParsePrecedence::PrecedenceCall => 10,
-            ParsePrecedence::PrecedencePrimary => 11,
-            _ => unreachable!(),
+            ParsePrecedence::PrecedenceConditional => 3,
+            ParsePrecedence::PrecedenceCoalesce => 4,
+            ParsePrecedence::PrecedenceOr => 5,
+            ParsePrecedence::PrecedenceAnd => 6,
+            ParsePrecedence::PrecedenceEquality => 7,
+            ParsePrecedence::PrecedenceComparison => 8,
+            ParsePrecedence::PrecedenceRange => 9,
+            ParsePrecedence::PrecedenceBitOr => 10,
+            ParsePrecedence::PrecedenceBitXor => 11,
+            ParsePrecedence::PrecedenceBitAnd => 12,
+            ParsePrecedence::PrecedenceShift => 13,
+            ParsePrecedence::PrecedenceTerm => 14,
+            ParsePrecedence::PrecedenceFactor => 15,
+            ParsePrecedence::PrecedenceUnary => 16,
+            ParsePrecedence::PrecedenceExponent => 17,
+            ParsePrecedence::PrecedenceCall => 18,
+            ParsePrecedence::PrecedencePrimary => 19,
         }
     }
 
@@ -86,20 +117,63 @@ impl ParsePrecedence {
 
 impl Parser {
     pub fn get_rule(&self, tok_type: &TokenType) -> &ParseRule {
-        self.parse_rules.get(tok_type).unwrap().clone()
+        self.parse_rules.get(tok_type).unwrap()
     }
 
     pub fn rules(&mut self) -> HashMap<TokenType, ParseRule> {
         let mut h = HashMap::new();
         h.insert(
             TokenLeftParen,
-            ParseRule { prefix: Some(Parser::grouping), infix: None, precedence: ParsePrecedence::PrecedenceNone },
+            ParseRule {
+                prefix: Some(Parser::grouping),
+                infix: Some(Parser::call),
+                precedence: ParsePrecedence::PrecedenceCall,
+            },
         );
         h.insert(TokenRightParen, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
-        h.insert(TokenLeftBrace, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(
+            TokenLeftBrace,
+            ParseRule { prefix: Some(Parser::map), infix: None, precedence: ParsePrecedence::PrecedenceNone },
+        );
         h.insert(TokenRightBrace, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(
+            TokenLeftBracket,
+            ParseRule {
+                prefix: Some(Parser::list),
+                infix: Some(Parser::index),
+                precedence: ParsePrecedence::PrecedenceCall,
+            },
+        );
+        h.insert(
+            TokenRightBracket,
+            ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone },
+        );
         h.insert(TokenComma, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
-        h.insert(TokenDot, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenColon, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(
+            TokenDot,
+            ParseRule { prefix: None, infix: Some(Parser::dot), precedence: ParsePrecedence::PrecedenceCall },
+        );
+        h.insert(
+            TokenQuestionDot,
+            ParseRule {
+                prefix: None,
+                infix: Some(Parser::optional_chain),
+                precedence: ParsePrecedence::PrecedenceCall,
+            },
+        );
+        h.insert(
+            TokenQuestion,
+            ParseRule {
+                prefix: None,
+                infix: Some(Parser::conditional),
+                precedence: ParsePrecedence::PrecedenceConditional,
+            },
+        );
+        h.insert(
+            TokenQuestionQuestion,
+            ParseRule { prefix: None, infix: Some(Parser::coalesce), precedence: ParsePrecedence::PrecedenceCoalesce },
+        );
         h.insert(
             TokenMinus,
             ParseRule {
@@ -117,10 +191,55 @@ impl Parser {
             TokenSlash,
             ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceFactor },
         );
+        h.insert(
+            TokenDiv,
+            ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceFactor },
+        );
         h.insert(
             TokenStar,
             ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceFactor },
         );
+        h.insert(
+            TokenPercent,
+            ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceFactor },
+        );
+        h.insert(
+            TokenStarStar,
+            ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceExponent },
+        );
+        h.insert(
+            TokenAmpersand,
+            ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceBitAnd },
+        );
+        h.insert(
+            TokenPipe,
+            ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceBitOr },
+        );
+        h.insert(
+            TokenCaret,
+            ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceBitXor },
+        );
+        h.insert(
+            TokenTilde,
+            ParseRule { prefix: Some(Parser::unary), infix: None, precedence: ParsePrecedence::PrecedenceNone },
+        );
+        h.insert(
+            TokenLessLess,
+            ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceShift },
+        );
+        h.insert(
+            TokenGreaterGreater,
+            ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceShift },
+        );
+        h.insert(
+            TokenDotDot,
+            ParseRule { prefix: None, infix: Some(Parser::range), precedence: ParsePrecedence::PrecedenceRange },
+        );
+        h.insert(
+            TokenDotDotEqual,
+            ParseRule { prefix: None, infix: Some(Parser::range), precedence: ParsePrecedence::PrecedenceRange },
+        );
+        h.insert(TokenDotDotDot, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
         h.insert(
             TokenBang,
             ParseRule { prefix: Some(Parser::unary), infix: None, precedence: ParsePrecedence::PrecedenceNone },
@@ -134,6 +253,18 @@ impl Parser {
             TokenEqualEqual,
             ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceEquality },
         );
+        h.insert(TokenPlusEqual, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenMinusEqual, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenStarEqual, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenSlashEqual, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(
+            TokenPercentEqual,
+            ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone },
+        );
+        h.insert(
+            TokenEqualGreater,
+            ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone },
+        );
         h.insert(
             TokenGreater,
             ParseRule { prefix: None, infix: Some(Parser::binary), precedence: ParsePrecedence::PrecedenceComparison },
@@ -166,15 +297,45 @@ impl Parser {
             TokenAnd,
             ParseRule { prefix: None, infix: Some(Parser::and_), precedence: ParsePrecedence::PrecedenceAnd },
         );
+        h.insert(TokenAsync, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(
+            TokenAwait,
+            ParseRule { prefix: Some(Parser::await_), infix: None, precedence: ParsePrecedence::PrecedenceNone },
+        );
+        h.insert(TokenBreak, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenContinue, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenCatch, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenFinally, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenThrow, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenTry, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
         h.insert(TokenClass, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenTrait, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenEnum, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenImport, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(
+            TokenMatch,
+            ParseRule {
+                prefix: Some(Parser::match_expression),
+                infix: None,
+                precedence: ParsePrecedence::PrecedenceNone,
+            },
+        );
         h.insert(TokenElse, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
         h.insert(
             TokenFalse,
             ParseRule { prefix: Some(Parser::literal), infix: None, precedence: ParsePrecedence::PrecedenceNone },
         );
         h.insert(TokenFor, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
-        h.insert(TokenFun, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(
+            TokenFun,
+            ParseRule {
+                prefix: Some(Parser::fun_expression),
+                infix: None,
+                precedence: ParsePrecedence::PrecedenceNone,
+            },
+        );
         h.insert(TokenIf, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenIn, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
         h.insert(
             TokenNil,
             ParseRule { prefix: Some(Parser::literal), infix: None, precedence: ParsePrecedence::PrecedenceNone },
@@ -185,14 +346,21 @@ impl Parser {
         );
         h.insert(TokenPrint, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
         h.insert(TokenReturn, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
-        h.insert(TokenSuper, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
-        h.insert(TokenThis, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(
+            TokenSuper,
+            ParseRule { prefix: Some(Parser::super_), infix: None, precedence: ParsePrecedence::PrecedenceNone },
+        );
+        h.insert(
+            TokenThis,
+            ParseRule { prefix: Some(Parser::this_), infix: None, precedence: ParsePrecedence::PrecedenceNone },
+        );
         h.insert(
             TokenTrue,
             ParseRule { prefix: Some(Parser::literal), infix: None, precedence: ParsePrecedence::PrecedenceNone },
         );
         h.insert(TokenVar, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
         h.insert(TokenWhile, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
+        h.insert(TokenYield, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
         h.insert(TokenError, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
         h.insert(TokenEof, ParseRule { prefix: None, infix: None, precedence: ParsePrecedence::PrecedenceNone });
 
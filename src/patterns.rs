@@ -0,0 +1,334 @@
+use crate::opcode::OpCode::*;
+use crate::parser::Parser;
+use crate::token::{Token, TokenType};
+use crate::token::TokenType::*;
+
+impl Parser {
+    /// Looks ahead from just after an opening `(` for `a, b) =`, the start of a destructuring
+    /// assignment rather than a grouping or tuple expression.
+    pub(crate) fn is_destructuring_assignment(&self) -> bool {
+        let mut lex = self.lex.clone();
+        let mut tok_type = self.curr_tok_type();
+        let mut depth = 1;
+        let mut has_comma = false;
+
+        loop {
+            match tok_type {
+                TokenLeftParen => depth += 1,
+                TokenRightParen => depth -= 1,
+                TokenComma if depth == 1 => has_comma = true,
+                TokenEof => return false,
+                _ => {}
+            }
+
+            if depth == 0 {
+                return has_comma && lex.scan_next().is(TokenEqual);
+            }
+            tok_type = lex.scan_next().token_type;
+        }
+    }
+
+    /// Compiles `(a, b) = value`, the expression evaluates to the unpacked value.
+    pub(crate) fn destructuring_assignment(&mut self) {
+        let names = self.pattern_names(TokenRightParen);
+        self.consume(&TokenEqual, "Expect '=' after destructuring pattern.");
+        self.expression();
+
+        self.codegen.emit_op(OpDup);
+        self.codegen.emit_op_operand(OpUnpackReversed, names.len() as u8);
+
+        for name in names.iter() {
+            let (_, set_op, arg) = self.resolve_variable(&name.raw);
+            self.codegen.emit_op_operand(set_op, arg);
+            self.codegen.emit_op(OpPop);
+        }
+    }
+
+    /// Compiles `var (a, b) = value;` and `var [a, b] = value;`, the pattern opener is consumed.
+    pub(crate) fn sequence_pattern_declaration(&mut self, close: TokenType) {
+        let names = self.pattern_names(close);
+        self.consume(&TokenEqual, "Expect '=' after destructuring pattern.");
+        self.expression();
+        self.consume(&TokenSemicolon, "Expect ';' after variable declaration.");
+
+        if self.scope.scope_depth > 0 {
+            self.codegen.emit_op_operand(OpUnpack, names.len() as u8);
+            for name in names.iter() {
+                self.add_local(name);
+            }
+            return;
+        }
+
+        self.codegen.emit_op_operand(OpUnpackReversed, names.len() as u8);
+        for name in names.iter() {
+            let global = self.name_const(&name.raw);
+            self.define_var(global as u8);
+        }
+    }
+
+    /// Compiles `var {a, b: c} = value;` binding `a = value["a"]` and `c = value["b"]`.
+    pub(crate) fn map_pattern_declaration(&mut self) {
+        let mut bindings: Vec<(String, Token)> = Vec::new();
+        while !self.curr_is(&TokenRightBrace) && !self.curr_is(&TokenEof) {
+            self.consume(&TokenIdentifier, "Expect key name in map pattern.");
+            let key = self.prev_tok.clone().unwrap();
+
+            let name = match self.match_advance(&TokenColon) {
+                true => {
+                    self.consume(&TokenIdentifier, "Expect variable name after ':'.");
+                    self.prev_tok.clone().unwrap()
+                }
+                false => key.clone(),
+            };
+            bindings.push((key.raw, name));
+
+            if !self.match_advance(&TokenComma) {
+                break;
+            }
+        }
+        self.consume(&TokenRightBrace, "Expect '}' after map pattern.");
+        self.consume(&TokenEqual, "Expect '=' after destructuring pattern.");
+        self.expression();
+        self.consume(&TokenSemicolon, "Expect ';' after variable declaration.");
+
+        if self.scope.scope_depth > 0 {
+            // the source stays in a hidden local so every binding lands in its own slot
+            self.scope.add_local(&"destructure source".to_string());
+            let source = self.scope.resolve_local("destructure source").unwrap();
+
+            for (key, name) in bindings {
+                self.codegen.emit_op_operand(OpGetLocal, source);
+                self.codegen.emit_const_string(key);
+                self.codegen.emit_op(OpIndexGet);
+                self.add_local(&name);
+            }
+            return;
+        }
+
+        for (key, name) in bindings {
+            self.codegen.emit_op(OpDup);
+            self.codegen.emit_const_string(key);
+            self.codegen.emit_op(OpIndexGet);
+
+            let global = self.name_const(&name.raw);
+            self.define_var(global as u8);
+        }
+        self.codegen.emit_op(OpPop);
+    }
+
+    /// Compiles `match (value) { pattern => expr, ... }`. The subject and the bindings of the arms are
+    /// locals above the temporaries of the enclosing expression, the arm that matches stores its value
+    /// in the subject's slot where the match leaves its result.
+    pub fn match_expression(&mut self, _can_assign: bool) {
+        let keyword = self.prev_tok.clone();
+        let declared = self.scope.take_from(self.codegen.stack_depth);
+        self.scope.begin_scope();
+        for _ in self.scope.locals.len()..self.codegen.stack_depth {
+            self.scope.add_local(&"match temporary".to_string());
+        }
+
+        self.consume(&TokenLeftParen, "Expect '(' after 'match'.");
+        self.expression();
+        self.consume(&TokenRightParen, "Expect ')' after match value.");
+        self.scope.add_local(&"match subject".to_string());
+        let subject = self.scope.resolve_local("match subject").unwrap();
+
+        self.consume(&TokenLeftBrace, "Expect '{' before match arms.");
+        let mut has_wildcard = false;
+        let mut end_jumps = Vec::new();
+        while !self.curr_is(&TokenRightBrace) && !self.curr_is(&TokenEof) {
+            let (catch_all, end_jump) = self.match_arm(subject);
+            has_wildcard |= catch_all;
+            end_jumps.push(end_jump);
+            if !self.match_advance(&TokenComma) {
+                break;
+            }
+        }
+        self.consume(&TokenRightBrace, "Expect '}' after match arms.");
+
+        if !has_wildcard {
+            self.warning_at(&keyword, "Match has no wildcard arm, unmatched values evaluate to nil.");
+        }
+        self.codegen.emit_op(OpPop);
+        self.codegen.emit_op(OpNil);
+        for jump in end_jumps {
+            self.codegen.patch_jump(jump);
+        }
+
+        // the subject's slot now holds the result and the temporaries belong to the enclosing expression
+        self.scope.end_scope();
+        self.scope.restore(declared);
+    }
+
+    /// Compiles `pattern [if guard] => expr`, returning whether the arm matches every value and the
+    /// jump to the end of the match taken after its value is stored.
+    fn match_arm(&mut self, subject: u8) -> (bool, usize) {
+        self.scope.begin_scope();
+        let catch_all = self.match_pattern(subject);
+
+        let guarded = self.match_advance(&TokenIf);
+        if guarded {
+            let skip_guard = self.codegen.emit_jump(OpJumpIfFalse);
+            self.codegen.emit_op(OpPop);
+            self.expression();
+            self.codegen.patch_jump(skip_guard);
+        }
+        self.consume(&TokenEqualGreater, "Expect '=>' after match pattern.");
+
+        let next_arm = self.codegen.emit_jump(OpJumpIfFalse);
+        let unmatched_depth = self.codegen.stack_depth;
+        self.codegen.emit_op(OpPop);
+        self.expression();
+        self.codegen.emit_op_operand(OpSetLocal, subject);
+        self.codegen.emit_op(OpPop);
+        let bindings = self.scope.count_deeper_than(self.scope.scope_depth - 1);
+        self.codegen.emit_op_operand(OpPopN, bindings);
+        let end_jump = self.codegen.emit_jump(OpJump);
+
+        self.codegen.patch_jump(next_arm);
+        self.codegen.stack_depth = unmatched_depth;
+        self.codegen.emit_op(OpPop);
+        self.codegen.emit_op_operand(OpPopN, self.scope.end_scope());
+
+        (catch_all && !guarded, end_jump)
+    }
+
+    /// Leaves whether the subject matches the pattern on the stack. A name binds the subject and
+    /// `_` ignores it, both match anything. `Enum.Variant(a, b)` matches a variant and binds its
+    /// payload, otherwise the pattern is literals, ranges and enum variants joined by `|`.
+    fn match_pattern(&mut self, subject: u8) -> bool {
+        let qualified = self.curr_is(&TokenIdentifier) && self.lookahead(0).is(&TokenDot);
+        if qualified && self.lookahead(2).is(&TokenLeftParen) {
+            self.variant_pattern(subject);
+            return false;
+        }
+        if self.curr_is(&TokenIdentifier) && !qualified {
+            self.advance();
+            let name = self.prev_tok.clone().unwrap();
+            if name.raw != "_" {
+                self.codegen.emit_op_operand(OpGetLocal, subject);
+                self.add_local(&name);
+            }
+            self.codegen.emit_op(OpTrue);
+            return true;
+        }
+
+        let mut matched_jumps = Vec::new();
+        loop {
+            self.literal_pattern(subject);
+            if !self.match_advance(&TokenPipe) {
+                break;
+            }
+
+            // an alternative matched, skip the rest keeping its `true`
+            let else_jump = self.codegen.emit_jump(OpJumpIfFalse);
+            matched_jumps.push(self.codegen.emit_jump(OpJump));
+            self.codegen.patch_jump(else_jump);
+            self.codegen.emit_op(OpPop);
+        }
+
+        for jump in matched_jumps {
+            self.codegen.patch_jump(jump);
+        }
+        false
+    }
+
+    /// Compiles `literal`, `start..end` or `start..=end` compared against the subject.
+    fn literal_pattern(&mut self, subject: u8) {
+        self.pattern_literal();
+
+        if self.match_advance(&TokenDotDot) || self.match_advance(&TokenDotDotEqual) {
+            let inclusive = self.prev_tok_type().is(&TokenDotDotEqual);
+            self.pattern_literal();
+            self.codegen.emit_op_operand(OpRange, inclusive as u8);
+            self.codegen.emit_op_operand(OpGetLocal, subject);
+            self.codegen.emit_op(OpInRange);
+            return;
+        }
+
+        self.codegen.emit_op_operand(OpGetLocal, subject);
+        self.codegen.emit_op(OpEqual);
+    }
+
+    fn pattern_literal(&mut self) {
+        let negate = self.match_advance(&TokenMinus);
+        self.advance();
+
+        match self.prev_tok_type() {
+            TokenNumber => self.number(false),
+            TokenString if !negate => self.string(false),
+            TokenTrue | TokenFalse | TokenNil if !negate => self.literal(false),
+            TokenIdentifier if !negate => self.enum_variant(),
+            _ => return self.error("Expect literal in match pattern."),
+        }
+
+        if negate {
+            self.codegen.emit_op(OpNegate);
+        }
+    }
+
+    /// Compiles `Enum.Variant(a, b)` matching the subject, `_` skips a value of the payload. The
+    /// payload values are bound whether the variant matched or not, `nil` when it didn't.
+    fn variant_pattern(&mut self, subject: u8) {
+        self.advance();
+        self.enum_variant();
+        self.consume(&TokenLeftParen, "Expect '(' after variant name.");
+        let names = match self.match_advance(&TokenRightParen) {
+            true => Vec::new(),
+            false => self.pattern_names(TokenRightParen),
+        };
+
+        self.codegen.emit_op_operand(OpGetLocal, subject);
+        self.codegen.emit_op_operand(OpMatchVariant, names.len() as u8);
+        for name in names {
+            match name.raw.as_str() {
+                "_" => self.scope.add_local(&name.raw),
+                _ => self.add_local(&name),
+            }
+        }
+    }
+
+    /// Compiles the `.Variant` after the enum name just consumed, reading the variant off the enum.
+    fn enum_variant(&mut self) {
+        let enum_name = self.prev_tok.clone().unwrap().raw;
+        self.load_variable(&enum_name);
+        self.consume(&TokenDot, "Expect '.' after enum name in match pattern.");
+        self.consume(&TokenIdentifier, "Expect variant name after '.'.");
+        let name = self.ident_const();
+        self.codegen.emit_op_operand(OpGetProperty, name as u8);
+    }
+
+    /// Type of the token `n` tokens after the current one.
+    fn lookahead(&self, n: usize) -> TokenType {
+        let mut lex = self.lex.clone();
+        let mut tok = lex.scan_next();
+        for _ in 0..n {
+            tok = lex.scan_next();
+        }
+        tok.token_type
+    }
+
+    /// Parses the comma separated variable names of a pattern up to and including `close`.
+    fn pattern_names(&mut self, close: TokenType) -> Vec<Token> {
+        let mut names = Vec::new();
+        loop {
+            self.consume(&TokenIdentifier, "Expect variable name in pattern.");
+            names.push(self.prev_tok.clone().unwrap());
+
+            if names.len() > u8::MAX as usize {
+                self.error("Can't have more than 255 variables in a pattern.");
+            }
+            if !self.match_advance(&TokenComma) || self.curr_is(&close) {
+                break;
+            }
+        }
+
+        let msg = match close {
+            TokenRightBracket => "Expect ']' after pattern.",
+            _ => "Expect ')' after pattern.",
+        };
+        self.consume(&close, msg);
+
+        names
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::object::TaskRef;
+use crate::value::Value;
+
+/// Time source of `sleep` timers. The virtual clock jumps straight to the next timer when every
+/// task is waiting, so scripts run without real delays and always interleave the same way.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Clock {
+    Real,
+    Virtual,
+}
+
+/// `sleep` task due at `wake`, `seq` orders timers due at the same time by when they started.
+struct Timer {
+    wake: u64,
+    seq: u64,
+    task: TaskRef,
+}
+
+/// Cooperative single-threaded scheduler. Tasks run one at a time until they await something
+/// unfinished, ready tasks are resumed in the order they became ready.
+pub struct Scheduler {
+    clock: Clock,
+    start: Instant,
+    // current time of the virtual clock in milliseconds
+    elapsed: u64,
+    ready: VecDeque<TaskRef>,
+    timers: Vec<Timer>,
+    next_seq: u64,
+    // failed tasks nobody has awaited yet, reported if the program ends before they are
+    pub unobserved: Vec<TaskRef>,
+}
+
+impl Scheduler {
+    pub fn new(clock: Clock) -> Self {
+        Self {
+            clock,
+            start: Instant::now(),
+            elapsed: 0,
+            ready: VecDeque::new(),
+            timers: Vec::new(),
+            next_seq: 0,
+            unobserved: Vec::new(),
+        }
+    }
+
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Milliseconds since the scheduler was created.
+    pub fn now(&self) -> u64 {
+        match self.clock {
+            Clock::Real => self.start.elapsed().as_millis() as u64,
+            Clock::Virtual => self.elapsed,
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.ready.is_empty() && self.timers.is_empty()
+    }
+
+    /// Starts a task that hasn't been started yet, an `async fun` call becomes ready to run and a
+    /// `sleep` timer starts counting down.
+    pub fn start(&mut self, task: &TaskRef) {
+        let mut started = task.borrow_mut();
+        if started.started {
+            return;
+        }
+        started.started = true;
+
+        match started.coroutine {
+            Some(_) => self.ready.push_back(task.clone()),
+            None => {
+                let wake = self.now() + started.delay;
+                self.timers.push(Timer { wake, seq: self.next_seq, task: task.clone() });
+                self.next_seq += 1;
+            }
+        }
+    }
+
+    /// Records how `task` ended and makes the tasks awaiting it ready.
+    pub fn complete(&mut self, task: &TaskRef, outcome: Result<Value, Value>) {
+        let mut completed = task.borrow_mut();
+        if outcome.is_err() && completed.waiters.is_empty() {
+            self.unobserved.push(task.clone());
+        }
+        completed.outcome = Some(outcome);
+        self.ready.extend(completed.waiters.drain(..));
+    }
+
+    /// Next task to resume, firing due timers first. When nothing is ready it waits for the
+    /// earliest timer, `None` means every remaining task waits on something that never completes.
+    pub fn next_task(&mut self) -> Option<TaskRef> {
+        loop {
+            self.fire_timers();
+            if let Some(task) = self.ready.pop_front() {
+                return Some(task);
+            }
+
+            let wake = self.timers.iter().map(|timer| timer.wake).min()?;
+            match self.clock {
+                Clock::Real => thread::sleep(Duration::from_millis(wake.saturating_sub(self.now()))),
+                Clock::Virtual => self.elapsed = self.elapsed.max(wake),
+            }
+        }
+    }
+
+    fn fire_timers(&mut self) {
+        let now = self.now();
+        let (mut due, pending): (Vec<Timer>, Vec<Timer>) =
+            std::mem::take(&mut self.timers).into_iter().partition(|timer| timer.wake <= now);
+        self.timers = pending;
+
+        due.sort_by_key(|timer| (timer.wake, timer.seq));
+        for timer in due {
+            self.complete(&timer.task, Ok(Value::new()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::bytecodes::Bytecodes;
+    use crate::function::{Closure, Function};
+    use crate::object::{Module, Task};
+
+    fn task(name: &str) -> TaskRef {
+        let function = Rc::new(Function::new(name, 0, 0, Bytecodes::new()));
+        let closure = Rc::new(Closure::new(function, Vec::new(), Rc::new(Module::new("main", None))));
+        Rc::new(RefCell::new(Task::new(closure, Vec::new(), Vec::new())))
+    }
+
+    /// Starts a `sleep(delay)` timer that `waiter` awaits.
+    fn sleep(scheduler: &mut Scheduler, delay: u64, waiter: &TaskRef) {
+        let timer = Rc::new(RefCell::new(Task::sleep(delay)));
+        timer.borrow_mut().waiters.push(waiter.clone());
+        scheduler.start(&timer);
+    }
+
+    /// Names of the tasks resumed until none is left, with the virtual time each resumed at.
+    fn run(scheduler: &mut Scheduler) -> Vec<(String, u64)> {
+        let mut order = Vec::new();
+        while let Some(task) = scheduler.next_task() {
+            order.push((task.borrow().name.clone(), scheduler.now()));
+        }
+        order
+    }
+
+    fn expected(order: &[(&str, u64)]) -> Vec<(String, u64)> {
+        order.iter().map(|(name, time)| (name.to_string(), *time)).collect()
+    }
+
+    #[test]
+    fn ready_tasks_run_in_start_order() {
+        let mut scheduler = Scheduler::new(Clock::Virtual);
+        let (a, b) = (task("a"), task("b"));
+        scheduler.start(&a);
+        scheduler.start(&b);
+        scheduler.start(&a);
+        assert_eq!(run(&mut scheduler), expected(&[("a", 0), ("b", 0)]));
+    }
+
+    #[test]
+    fn timers_wake_their_waiters_in_time_order() {
+        let mut scheduler = Scheduler::new(Clock::Virtual);
+        let (slow, fast, middle) = (task("slow"), task("fast"), task("middle"));
+        sleep(&mut scheduler, 30, &slow);
+        sleep(&mut scheduler, 10, &fast);
+        sleep(&mut scheduler, 20, &middle);
+        assert_eq!(run(&mut scheduler), expected(&[("fast", 10), ("middle", 20), ("slow", 30)]));
+        assert!(scheduler.is_idle());
+    }
+
+    #[test]
+    fn timers_due_on_the_same_tick_fire_in_start_order() {
+        let mut scheduler = Scheduler::new(Clock::Virtual);
+        let (first, second, third) = (task("first"), task("second"), task("third"));
+        sleep(&mut scheduler, 5, &first);
+        sleep(&mut scheduler, 5, &second);
+        sleep(&mut scheduler, 0, &third);
+        assert_eq!(run(&mut scheduler), expected(&[("third", 0), ("first", 5), ("second", 5)]));
+    }
+
+    #[test]
+    fn ready_tasks_run_before_timers_fire_later() {
+        let mut scheduler = Scheduler::new(Clock::Virtual);
+        let (ready, waiting) = (task("ready"), task("waiting"));
+        sleep(&mut scheduler, 1, &waiting);
+        scheduler.start(&ready);
+        assert_eq!(run(&mut scheduler), expected(&[("ready", 0), ("waiting", 1)]));
+    }
+
+    #[test]
+    fn completing_a_task_readies_its_waiters_in_order() {
+        let mut scheduler = Scheduler::new(Clock::Virtual);
+        let (awaited, x, y) = (task("awaited"), task("x"), task("y"));
+        awaited.borrow_mut().waiters.extend([x, y]);
+        scheduler.complete(&awaited, Ok(Value::new()));
+        assert_eq!(run(&mut scheduler), expected(&[("x", 0), ("y", 0)]));
+        assert!(scheduler.unobserved.is_empty());
+
+        let failed = task("failed");
+        scheduler.complete(&failed, Err(Value::new()));
+        assert_eq!(scheduler.unobserved.len(), 1);
+    }
+
+    #[test]
+    fn nothing_to_run_is_none() {
+        let mut scheduler = Scheduler::new(Clock::Virtual);
+        assert!(scheduler.next_task().is_none());
+        assert_eq!(scheduler.now(), 0);
+    }
+}
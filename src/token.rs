@@ -2,8 +2,10 @@ use std::fmt;
 use std::fmt::Formatter;
 
 use crate::token::TokenType::{
-    TokenAnd, TokenClass, TokenElse, TokenFalse, TokenFor, TokenFun, TokenIdentifier, TokenIf, TokenNil, TokenOr,
-    TokenPrint, TokenReturn, TokenSuper, TokenThis, TokenTrue, TokenVar, TokenWhile,
+    TokenAnd, TokenAsync, TokenAwait, TokenBreak, TokenCatch, TokenClass, TokenContinue, TokenDiv, TokenElse,
+    TokenEnum, TokenFalse, TokenFinally, TokenFor, TokenFun, TokenIdentifier, TokenIf, TokenImport, TokenIn, TokenMatch,
+    TokenNil, TokenOr, TokenPrint, TokenReturn, TokenSuper, TokenThis, TokenThrow, TokenTrait, TokenTrue, TokenTry,
+    TokenVar, TokenWhile, TokenYield,
 };
 
 #[derive(Debug, Clone)]
@@ -21,23 +23,46 @@ pub enum TokenType {
     TokenRightParen,
     TokenLeftBrace,
     TokenRightBrace,
+    TokenLeftBracket,
+    TokenRightBracket,
     TokenComma,
     TokenDot,
+    TokenColon,
     TokenMinus,
     TokenPlus,
     TokenSemicolon,
     TokenSlash,
     TokenStar,
+    TokenPercent,
+    TokenAmpersand,
+    TokenPipe,
+    TokenCaret,
+    TokenTilde,
 
     // One or two character tokens.
     TokenBang,
     TokenBangEqual,
     TokenEqual,
     TokenEqualEqual,
+    TokenEqualGreater,
+    TokenPlusEqual,
+    TokenMinusEqual,
+    TokenStarEqual,
+    TokenSlashEqual,
+    TokenPercentEqual,
+    TokenQuestion,
+    TokenQuestionQuestion,
+    TokenQuestionDot,
     TokenGreater,
     TokenGreaterEqual,
     TokenLess,
     TokenLessEqual,
+    TokenStarStar,
+    TokenLessLess,
+    TokenGreaterGreater,
+    TokenDotDot,
+    TokenDotDotEqual,
+    TokenDotDotDot,
 
     // Literals.
     TokenIdentifier,
@@ -46,21 +71,36 @@ pub enum TokenType {
 
     // Keywords.
     TokenAnd,
+    TokenAsync,
+    TokenAwait,
+    TokenBreak,
+    TokenCatch,
     TokenClass,
+    TokenContinue,
+    TokenDiv,
     TokenElse,
+    TokenEnum,
     TokenFalse,
+    TokenFinally,
     TokenFor,
     TokenFun,
     TokenIf,
+    TokenImport,
+    TokenIn,
+    TokenMatch,
     TokenNil,
     TokenOr,
     TokenPrint,
     TokenReturn,
     TokenSuper,
     TokenThis,
+    TokenThrow,
+    TokenTrait,
     TokenTrue,
+    TokenTry,
     TokenVar,
     TokenWhile,
+    TokenYield,
 
     TokenError,
     TokenEof,
@@ -79,9 +119,19 @@ impl Token {
 pub fn kw_type_from_str(token_type: &str) -> TokenType {
     match token_type {
         "and" => TokenAnd,
+        "async" => TokenAsync,
+        "await" => TokenAwait,
+        "break" => TokenBreak,
+        "catch" => TokenCatch,
         "class" => TokenClass,
+        "continue" => TokenContinue,
+        "div" => TokenDiv,
         "else" => TokenElse,
+        "enum" => TokenEnum,
         "if" => TokenIf,
+        "import" => TokenImport,
+        "in" => TokenIn,
+        "match" => TokenMatch,
         "nil" => TokenNil,
         "or" => TokenOr,
         "print" => TokenPrint,
@@ -89,11 +139,16 @@ pub fn kw_type_from_str(token_type: &str) -> TokenType {
         "super" => TokenSuper,
         "var" => TokenVar,
         "while" => TokenWhile,
+        "yield" => TokenYield,
         "false" => TokenFalse,
+        "finally" => TokenFinally,
         "for" => TokenFor,
         "fun" => TokenFun,
         "this" => TokenThis,
+        "throw" => TokenThrow,
+        "trait" => TokenTrait,
         "true" => TokenTrue,
+        "try" => TokenTry,
         _ => TokenIdentifier,
     }
 }
@@ -1,18 +1,41 @@
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Formatter;
-use std::ops::{Add, Div, Mul, Neg, Sub};
-use std::ptr::write;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
+use std::rc::Rc;
 
-use crate::Bytecodes;
+use crate::function::{Closure, Function, NativeFunction};
+use crate::object::{
+    sequence_index, BoundMethod, BuiltinMethod, Class, Enum, GeneratorRef, Instance, Iter, ListRef, Map, MapRef,
+    Module, Range, TaskRef, Variant,
+};
+
+/// Longest string in bytes that repeating a string may build.
+pub const STRING_MAX: usize = 1 << 30;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub enum ValueKind {
     Nil,
     Bool,
     Number,
+    Integer,
     String,
     Function,
+    Native,
+    Tuple,
+    List,
+    Map,
+    Range,
+    Iterator,
+    Class,
+    Instance,
+    BoundMethod,
+    Generator,
+    Task,
+    Enum,
+    Variant,
+    Module,
 }
 
 #[derive(Debug, Clone)]
@@ -20,66 +43,370 @@ pub enum ValueRepr {
     Nil(),
     Boolean(bool),
     Number(f64),
+    Integer(i64),
     String(String),
+    Native(Rc<NativeFunction>),
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    Tuple(Rc<Vec<Value>>),
+    List(ListRef),
+    Map(MapRef),
+    Range(Range),
+    Iterator(Rc<RefCell<Iter>>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    BoundMethod(Rc<BoundMethod>),
+    BuiltinMethod(Rc<BuiltinMethod>),
+    Generator(GeneratorRef),
+    Task(TaskRef),
+    Enum(Rc<Enum>),
+    Variant(Rc<Variant>),
+    Module(Rc<Module>),
 }
 
 #[derive(Debug, Clone)]
 pub struct Value(pub ValueRepr);
 
+/// Result of an operation on values, the error holds the runtime error message.
+pub type ValueResult = Result<Value, String>;
+
+impl Value {
+    /// Applies an arithmetic operator, keeping integers when both operands are integers
+    /// and promoting to float otherwise.
+    fn arith(self, rhs: Self, int_op: fn(i64, i64) -> Option<i64>, float_op: fn(f64, f64) -> f64) -> ValueResult {
+        match (&self.0, &rhs.0) {
+            (ValueRepr::Integer(l), ValueRepr::Integer(r)) => match int_op(*l, *r) {
+                Some(v) => Ok(Value(ValueRepr::Integer(v))),
+                None => Err("Integer overflow.".to_string()),
+            },
+            _ => match (self.as_f64(), rhs.as_f64()) {
+                (Some(l), Some(r)) => Ok(Value(ValueRepr::Number(float_op(l, r)))),
+                _ => Err("Operands must be numbers.".to_string()),
+            },
+        }
+    }
+
+    /// Floor division, `//`. Integers stay integers and a zero divisor is an error.
+    pub fn floor_div(self, rhs: Self) -> ValueResult {
+        match (&self.0, &rhs.0) {
+            (ValueRepr::Integer(_), ValueRepr::Integer(0)) => Err("Integer division by zero.".to_string()),
+            _ => self.arith(
+                rhs,
+                |l, r| {
+                    let q = l.checked_div(r)?;
+                    if l % r != 0 && (l < 0) != (r < 0) {
+                        return q.checked_sub(1);
+                    }
+                    Some(q)
+                },
+                |l, r| (l / r).floor(),
+            ),
+        }
+    }
+
+    /// Exponentiation, `**`. A negative integer exponent yields a float.
+    pub fn pow(self, rhs: Self) -> ValueResult {
+        match (&self.0, &rhs.0) {
+            (ValueRepr::Integer(l), ValueRepr::Integer(r)) if *r >= 0 => {
+                match u32::try_from(*r).ok().and_then(|r| l.checked_pow(r)) {
+                    Some(v) => Ok(Value(ValueRepr::Integer(v))),
+                    None => Err("Integer overflow.".to_string()),
+                }
+            }
+            _ => match (self.as_f64(), rhs.as_f64()) {
+                (Some(l), Some(r)) => Ok(Value(ValueRepr::Number(l.powf(r)))),
+                _ => Err("Operands must be numbers.".to_string()),
+            },
+        }
+    }
+
+    /// Applies a bitwise operator, only defined for integers.
+    fn bitwise(self, rhs: Self, op: fn(i64, i64) -> Option<i64>) -> ValueResult {
+        match (&self.0, &rhs.0) {
+            (ValueRepr::Integer(l), ValueRepr::Integer(r)) => match op(*l, *r) {
+                Some(v) => Ok(Value(ValueRepr::Integer(v))),
+                None => Err("Shift amount out of range.".to_string()),
+            },
+            _ => Err("Operands must be integers.".to_string()),
+        }
+    }
+
+    pub fn list(items: Vec<Value>) -> Self {
+        Value(ValueRepr::List(Rc::new(RefCell::new(items))))
+    }
+
+    pub fn tuple(items: Vec<Value>) -> Self {
+        Value(ValueRepr::Tuple(Rc::new(items)))
+    }
+
+    pub fn map(map: Map) -> Self {
+        Value(ValueRepr::Map(Rc::new(RefCell::new(map))))
+    }
+
+    /// Reads `self[index]` from a list, map or string.
+    pub fn get_index(&self, index: &Value) -> ValueResult {
+        match &self.0 {
+            ValueRepr::List(list) => {
+                let list = list.borrow();
+                let idx = sequence_index(index, list.len())?;
+                Ok(list[idx].clone())
+            }
+            ValueRepr::Tuple(items) => {
+                let idx = sequence_index(index, items.len())?;
+                Ok(items[idx].clone())
+            }
+            ValueRepr::Map(map) => Ok(map.borrow().get(index)?.unwrap_or_default()),
+            ValueRepr::String(str) => {
+                let chars: Vec<char> = str.chars().collect();
+                let idx = sequence_index(index, chars.len())?;
+                Ok(Value(ValueRepr::String(chars[idx].to_string())))
+            }
+            other => Err(format!("Cannot index into {}.", other.kind())),
+        }
+    }
+
+    /// Stores `self[index] = value` into a list or map.
+    pub fn set_index(&self, index: Value, value: Value) -> Result<(), String> {
+        match &self.0 {
+            ValueRepr::List(list) => {
+                let mut list = list.borrow_mut();
+                let idx = sequence_index(&index, list.len())?;
+                list[idx] = value;
+                Ok(())
+            }
+            ValueRepr::Map(map) => map.borrow_mut().set(index, value),
+            ValueRepr::Tuple(_) => Err("Tuples are immutable.".to_string()),
+            other => Err(format!("Cannot assign to an index of {}.", other.kind())),
+        }
+    }
+
+    /// Reads `self.name`, a field of the instance or one of its class' methods bound to it, or a
+    /// static member of a class.
+    pub fn get_property(&self, name: &str) -> ValueResult {
+        let instance = match &self.0 {
+            ValueRepr::Instance(instance) => instance,
+            ValueRepr::Generator(_) if name == "next" || name == "done" => {
+                return Ok(Value(ValueRepr::BuiltinMethod(Rc::new(BuiltinMethod::new(self.clone(), name)))));
+            }
+            ValueRepr::Task(_) if name == "done" => {
+                return Ok(Value(ValueRepr::BuiltinMethod(Rc::new(BuiltinMethod::new(self.clone(), name)))));
+            }
+            ValueRepr::Generator(_) | ValueRepr::Task(_) => return Err(format!("Undefined property '{}'.", name)),
+            ValueRepr::Class(class) => {
+                return class.statics.borrow().get(name).cloned().ok_or(format!("Undefined property '{}'.", name));
+            }
+            ValueRepr::Enum(enum_) => {
+                return match enum_.variants.iter().position(|variant| variant.name == name) {
+                    Some(index) => {
+                        let variant = Variant::new(enum_.clone(), index, Vec::new());
+                        Ok(Value(ValueRepr::Variant(Rc::new(variant))))
+                    }
+                    None => Err(format!("Undefined variant '{}' of {}.", name, enum_.name)),
+                };
+            }
+            ValueRepr::Variant(variant) if !variant.is_constructor() => {
+                return match variant.decl().fields.iter().position(|field| field == name) {
+                    Some(index) => Ok(variant.values[index].clone()),
+                    None => Err(format!("Undefined property '{}'.", name)),
+                };
+            }
+            ValueRepr::Module(module) => {
+                let globals = module.globals.borrow();
+                return globals.get(name).cloned().ok_or(format!("Module '{}' has no member '{}'.", module.name, name));
+            }
+            _ => return Err("Only instances have properties.".to_string()),
+        };
+
+        if let Some(value) = instance.fields.borrow().get(name) {
+            return Ok(value.clone());
+        }
+        match instance.class.find_method(name) {
+            Some(method) => Ok(Value(ValueRepr::BoundMethod(Rc::new(BoundMethod::new(self.clone(), method))))),
+            None => Err(format!("Undefined property '{}'.", name)),
+        }
+    }
+
+    /// Stores `self.name = value` into a field of the instance, or a static field of the class.
+    pub fn set_property(&self, name: &str, value: Value) -> Result<(), String> {
+        match &self.0 {
+            ValueRepr::Instance(instance) => {
+                instance.fields.borrow_mut().insert(name.to_string(), value);
+                Ok(())
+            }
+            ValueRepr::Class(class) => {
+                class.statics.borrow_mut().insert(name.to_string(), value);
+                Ok(())
+            }
+            ValueRepr::Module(module) => Err(format!("Can't assign to member '{}' of module '{}'.", name, module.name)),
+            _ => Err("Only instances have fields.".to_string()),
+        }
+    }
+
+    /// Tests whether `value` is one of the integers in the range `self`, anything else is not contained.
+    pub fn range_contains(&self, value: &Value) -> bool {
+        let range = match &self.0 {
+            ValueRepr::Range(range) => range,
+            _ => return false,
+        };
+
+        match value.0 {
+            ValueRepr::Integer(v) => range.contains(v),
+            ValueRepr::Number(v) if v.fract() == 0.0 => range.contains(v as i64),
+            _ => false,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.0 {
+            ValueRepr::Number(v) => Some(v),
+            ValueRepr::Integer(v) => Some(v as f64),
+            _ => None,
+        }
+    }
+}
+
 impl Neg for Value {
-    type Output = Self;
+    type Output = ValueResult;
 
     fn neg(self) -> Self::Output {
         match self.0 {
-            ValueRepr::Boolean(v) => Value(ValueRepr::Boolean(!v)),
-            ValueRepr::Number(v) => Value(ValueRepr::Number(-v)),
-            _ => Value(ValueRepr::Nil()),
+            ValueRepr::Boolean(v) => Ok(Value(ValueRepr::Boolean(!v))),
+            ValueRepr::Number(v) => Ok(Value(ValueRepr::Number(-v))),
+            ValueRepr::Integer(v) => match v.checked_neg() {
+                Some(v) => Ok(Value(ValueRepr::Integer(v))),
+                None => Err("Integer overflow.".to_string()),
+            },
+            _ => Err("Operand must be a number.".to_string()),
         }
     }
 }
 
 impl Add for Value {
-    type Output = Self;
+    type Output = ValueResult;
 
     fn add(self, rhs: Self) -> Self::Output {
-        match (self.0, rhs.0) {
-            (ValueRepr::Number(l), ValueRepr::Number(r)) => Value(ValueRepr::Number(l + r)),
-            (ValueRepr::String(l), ValueRepr::String(r)) => Value(ValueRepr::String(l + &r)),
-            _ => Value(ValueRepr::Nil()),
+        match (&self.0, &rhs.0) {
+            (ValueRepr::String(l), ValueRepr::String(r)) => Ok(Value(ValueRepr::String(l.to_owned() + r))),
+            (ValueRepr::String(_), _) | (_, ValueRepr::String(_)) => {
+                Err("Operands must be two numbers or two strings.".to_string())
+            }
+            _ => self.arith(rhs, i64::checked_add, |l, r| l + r),
         }
     }
 }
 
 impl Sub for Value {
-    type Output = Self;
+    type Output = ValueResult;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        match (self.0, rhs.0) {
-            (ValueRepr::Number(l), ValueRepr::Number(r)) => Value(ValueRepr::Number(l - r)),
-            _ => Value(ValueRepr::Nil()),
-        }
+        self.arith(rhs, i64::checked_sub, |l, r| l - r)
     }
 }
 
 impl Mul for Value {
-    type Output = Self;
+    type Output = ValueResult;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        match (self.0, rhs.0) {
-            (ValueRepr::Number(l), ValueRepr::Number(r)) => Value(ValueRepr::Number(l * r)),
-            (ValueRepr::String(l), ValueRepr::Number(t)) => Value(ValueRepr::String(l.repeat(t as usize))),
-            _ => Value(ValueRepr::Nil()),
+        match (&self.0, &rhs.0) {
+            (ValueRepr::String(l), ValueRepr::Integer(t)) => {
+                let count = (*t).max(0) as usize;
+                match l.len().checked_mul(count) {
+                    Some(len) if len <= STRING_MAX => Ok(Value(ValueRepr::String(l.repeat(count)))),
+                    _ => Err("String is too long.".to_string()),
+                }
+            }
+            _ => self.arith(rhs, i64::checked_mul, |l, r| l * r),
         }
     }
 }
 
 impl Div for Value {
-    type Output = Self;
+    type Output = ValueResult;
 
     fn div(self, rhs: Self) -> Self::Output {
-        match (self.0, rhs.0) {
-            (ValueRepr::Number(l), ValueRepr::Number(r)) => Value(ValueRepr::Number(l / r)),
-            _ => Value(ValueRepr::Nil()),
+        match (self.as_f64(), rhs.as_f64()) {
+            (Some(l), Some(r)) => Ok(Value(ValueRepr::Number(l / r))),
+            _ => Err("Operands must be numbers.".to_string()),
+        }
+    }
+}
+
+impl Rem for Value {
+    type Output = ValueResult;
+
+    /// The remainder takes the sign of the divisor so that `a == (a // b) * b + a % b`.
+    fn rem(self, rhs: Self) -> Self::Output {
+        match (&self.0, &rhs.0) {
+            (ValueRepr::Integer(_), ValueRepr::Integer(0)) => Err("Integer modulo by zero.".to_string()),
+            _ => self.arith(
+                rhs,
+                |l, r| {
+                    let m = l.checked_rem(r)?;
+                    if m != 0 && (m < 0) != (r < 0) {
+                        return m.checked_add(r);
+                    }
+                    Some(m)
+                },
+                |l, r| {
+                    let m = l % r;
+                    if m != 0.0 && (m < 0.0) != (r < 0.0) {
+                        return m + r;
+                    }
+                    m
+                },
+            ),
+        }
+    }
+}
+
+impl BitAnd for Value {
+    type Output = ValueResult;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.bitwise(rhs, |l, r| Some(l & r))
+    }
+}
+
+impl BitOr for Value {
+    type Output = ValueResult;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.bitwise(rhs, |l, r| Some(l | r))
+    }
+}
+
+impl BitXor for Value {
+    type Output = ValueResult;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        self.bitwise(rhs, |l, r| Some(l ^ r))
+    }
+}
+
+impl Shl for Value {
+    type Output = ValueResult;
+
+    fn shl(self, rhs: Self) -> Self::Output {
+        self.bitwise(rhs, |l, r| l.checked_shl(u32::try_from(r).ok()?))
+    }
+}
+
+impl Shr for Value {
+    type Output = ValueResult;
+
+    fn shr(self, rhs: Self) -> Self::Output {
+        self.bitwise(rhs, |l, r| l.checked_shr(u32::try_from(r).ok()?))
+    }
+}
+
+impl Not for Value {
+    type Output = ValueResult;
+
+    /// Bitwise complement, `~`.
+    fn not(self) -> Self::Output {
+        match self.0 {
+            ValueRepr::Integer(v) => Ok(Value(ValueRepr::Integer(!v))),
+            _ => Err("Operand must be an integer.".to_string()),
         }
     }
 }
@@ -87,8 +414,36 @@ impl Div for Value {
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (&self.0, &other.0) {
-            (ValueRepr::Number(l), ValueRepr::Number(r)) => l == r,
+            (ValueRepr::Integer(l), ValueRepr::Integer(r)) => l == r,
+            (ValueRepr::Number(_) | ValueRepr::Integer(_), ValueRepr::Number(_) | ValueRepr::Integer(_)) => {
+                self.as_f64() == other.as_f64()
+            }
             (ValueRepr::Boolean(l), ValueRepr::Boolean(r)) => l == r,
+            (ValueRepr::String(l), ValueRepr::String(r)) => l == r,
+            (ValueRepr::Native(l), ValueRepr::Native(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::Function(l), ValueRepr::Function(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::Closure(l), ValueRepr::Closure(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::Tuple(l), ValueRepr::Tuple(r)) => l == r,
+            (ValueRepr::List(l), ValueRepr::List(r)) => Rc::ptr_eq(l, r) || *l.borrow() == *r.borrow(),
+            (ValueRepr::Map(l), ValueRepr::Map(r)) => {
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len() && l.entries().iter().all(|(k, v)| r.get(k).ok().flatten().as_ref() == Some(v))
+            }
+            (ValueRepr::Range(l), ValueRepr::Range(r)) => l == r,
+            (ValueRepr::Iterator(l), ValueRepr::Iterator(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::Class(l), ValueRepr::Class(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::Instance(l), ValueRepr::Instance(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::BoundMethod(l), ValueRepr::BoundMethod(r)) => {
+                l.receiver == r.receiver && Rc::ptr_eq(&l.method, &r.method)
+            }
+            (ValueRepr::BuiltinMethod(l), ValueRepr::BuiltinMethod(r)) => l.receiver == r.receiver && l.name == r.name,
+            (ValueRepr::Generator(l), ValueRepr::Generator(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::Task(l), ValueRepr::Task(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::Enum(l), ValueRepr::Enum(r)) => Rc::ptr_eq(l, r),
+            (ValueRepr::Variant(l), ValueRepr::Variant(r)) => {
+                Rc::ptr_eq(&l.enum_, &r.enum_) && l.index == r.index && l.values == r.values
+            }
+            (ValueRepr::Module(l), ValueRepr::Module(r)) => Rc::ptr_eq(l, r),
             (ValueRepr::Nil(), ValueRepr::Nil()) => true,
             _ => false,
         }
@@ -98,23 +453,9 @@ impl PartialEq for Value {
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (&self.0, &other.0) {
-            (ValueRepr::Number(l), ValueRepr::Number(r)) => {
-                if l < r {
-                    return Some(Ordering::Less);
-                } else if l > r {
-                    return Some(Ordering::Greater);
-                }
-                return Some(Ordering::Equal);
-            }
-            (ValueRepr::String(l), ValueRepr::String(r)) => {
-                if l < r {
-                    return Some(Ordering::Less);
-                } else if l > r {
-                    return Some(Ordering::Greater);
-                }
-                return Some(Ordering::Equal);
-            }
-            _ => None,
+            (ValueRepr::Integer(l), ValueRepr::Integer(r)) => Some(l.cmp(r)),
+            (ValueRepr::String(l), ValueRepr::String(r)) => Some(l.cmp(r)),
+            _ => self.as_f64()?.partial_cmp(&other.as_f64()?),
         }
     }
 }
@@ -124,7 +465,23 @@ impl ValueRepr {
         match &self {
             ValueRepr::Boolean(_) => ValueKind::Bool,
             ValueRepr::Number(_) => ValueKind::Number,
+            ValueRepr::Integer(_) => ValueKind::Integer,
             ValueRepr::String(_) => ValueKind::String,
+            ValueRepr::Native(_) => ValueKind::Native,
+            ValueRepr::Function(_) | ValueRepr::Closure(_) => ValueKind::Function,
+            ValueRepr::Tuple(_) => ValueKind::Tuple,
+            ValueRepr::List(_) => ValueKind::List,
+            ValueRepr::Map(_) => ValueKind::Map,
+            ValueRepr::Range(_) => ValueKind::Range,
+            ValueRepr::Iterator(_) => ValueKind::Iterator,
+            ValueRepr::Class(_) => ValueKind::Class,
+            ValueRepr::Instance(_) => ValueKind::Instance,
+            ValueRepr::BoundMethod(_) | ValueRepr::BuiltinMethod(_) => ValueKind::BoundMethod,
+            ValueRepr::Generator(_) => ValueKind::Generator,
+            ValueRepr::Task(_) => ValueKind::Task,
+            ValueRepr::Enum(_) => ValueKind::Enum,
+            ValueRepr::Variant(_) => ValueKind::Variant,
+            ValueRepr::Module(_) => ValueKind::Module,
             ValueRepr::Nil() => ValueKind::Nil,
         }
     }
@@ -132,27 +489,152 @@ impl ValueRepr {
 
 impl Default for ValueRepr {
     fn default() -> ValueRepr {
-        ValueRepr::Nil().into()
+        ValueRepr::Nil()
+    }
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ValueKind::Nil => "nil",
+                ValueKind::Bool => "bool",
+                ValueKind::Number => "float",
+                ValueKind::Integer => "int",
+                ValueKind::String => "string",
+                ValueKind::Function => "function",
+                ValueKind::Native => "native function",
+                ValueKind::Tuple => "tuple",
+                ValueKind::List => "list",
+                ValueKind::Map => "map",
+                ValueKind::Range => "range",
+                ValueKind::Iterator => "iterator",
+                ValueKind::Class => "class",
+                ValueKind::Instance => "instance",
+                ValueKind::BoundMethod => "bound method",
+                ValueKind::Generator => "generator",
+                ValueKind::Task => "task",
+                ValueKind::Enum => "enum",
+                ValueKind::Variant => "enum variant",
+                ValueKind::Module => "module",
+            }
+        )
     }
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match &self.0 {
-            ValueRepr::Boolean(val) => write!(f, "{}", val),
-            ValueRepr::Number(val) => write!(f, "{}", val),
-            ValueRepr::String(val) => write!(f, "{}", val),
-            ValueRepr::Nil() => write!(f, "NIL"),
-        }
+        f.write_str(&self.format(&mut |_| None))
+    }
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Value {
     pub fn new() -> Self {
-        Self { 0: Default::default() }
+        Self(Default::default())
     }
 
     pub fn print(&self) {
         println!("{}", self)
     }
+
+    /// Formats the value as `print` shows it. `to_string` gives the text of an instance that defines its
+    /// own, such as with a `toString` method, `None` falls back to `<class> instance`.
+    pub fn format(&self, to_string: &mut dyn FnMut(&Value) -> Option<String>) -> String {
+        self.format_in(to_string, &mut Vec::new())
+    }
+
+    /// Formats the value inside the lists and maps of `seen`, one already there is shown as `[...]` or
+    /// `{...}` so that a collection containing itself still prints.
+    fn format_in(&self, to_string: &mut dyn FnMut(&Value) -> Option<String>, seen: &mut Vec<*const ()>) -> String {
+        match &self.0 {
+            ValueRepr::Boolean(val) => val.to_string(),
+            // Debug formatting keeps the fraction (`2.0`) so floats never read as integers.
+            ValueRepr::Number(val) => format!("{:?}", val),
+            ValueRepr::Integer(val) => val.to_string(),
+            ValueRepr::String(val) => val.clone(),
+            ValueRepr::Native(native) => format!("<native fn {}>", native.name),
+            ValueRepr::Function(function) => format!("<fn {}>", function.name),
+            ValueRepr::Closure(closure) => format!("<fn {}>", closure.function.name),
+            ValueRepr::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.repr_with(to_string, seen)).collect();
+                match items.len() {
+                    1 => format!("({},)", items[0]),
+                    _ => format!("({})", items.join(", ")),
+                }
+            }
+            ValueRepr::List(list) => {
+                let ptr = Rc::as_ptr(list) as *const ();
+                if seen.contains(&ptr) {
+                    return "[...]".to_string();
+                }
+                seen.push(ptr);
+                // a `toString` may change the list while it's formatted
+                let items = list.borrow().clone();
+                let items: Vec<String> = items.iter().map(|item| item.repr_with(to_string, seen)).collect();
+                seen.pop();
+                format!("[{}]", items.join(", "))
+            }
+            ValueRepr::Map(map) => {
+                let ptr = Rc::as_ptr(map) as *const ();
+                if seen.contains(&ptr) {
+                    return "{...}".to_string();
+                }
+                seen.push(ptr);
+                let entries = map.borrow().entries().to_vec();
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.repr_with(to_string, seen), v.repr_with(to_string, seen)))
+                    .collect();
+                seen.pop();
+                format!("{{{}}}", entries.join(", "))
+            }
+            ValueRepr::Range(range) => {
+                let bounds = format!("{}{}{}", range.start, if range.inclusive { "..=" } else { ".." }, range.end);
+                match range.step {
+                    1 => bounds,
+                    step => format!("{} step {}", bounds, step),
+                }
+            }
+            ValueRepr::Iterator(_) => "<iterator>".to_string(),
+            ValueRepr::Class(class) => class.name.clone(),
+            ValueRepr::Instance(instance) => {
+                to_string(self).unwrap_or_else(|| format!("{} instance", instance.class.name))
+            }
+            ValueRepr::BoundMethod(bound) => format!("<fn {}>", bound.method.function.name),
+            ValueRepr::BuiltinMethod(method) => format!("<native fn {}>", method.name),
+            ValueRepr::Generator(generator) => format!("<generator {}>", generator.borrow().closure.function.name),
+            ValueRepr::Task(task) => format!("<task {}>", task.borrow().name),
+            ValueRepr::Enum(enum_) => enum_.name.clone(),
+            ValueRepr::Variant(variant) => {
+                let name = format!("{}.{}", variant.enum_.name, variant.decl().name);
+                if variant.values.is_empty() {
+                    return name;
+                }
+                let values: Vec<String> = variant.values.iter().map(|value| value.repr_with(to_string, seen)).collect();
+                format!("{}({})", name, values.join(", "))
+            }
+            ValueRepr::Module(module) => format!("<module {}>", module.name),
+            ValueRepr::Nil() => "NIL".to_string(),
+        }
+    }
+
+    /// Formats the value as it appears nested inside a collection, strings are quoted.
+    pub fn repr(&self) -> String {
+        self.repr_with(&mut |_| None, &mut Vec::new())
+    }
+
+    fn repr_with(&self, to_string: &mut dyn FnMut(&Value) -> Option<String>, seen: &mut Vec<*const ()>) -> String {
+        match &self.0 {
+            ValueRepr::String(str) => format!("{:?}", str),
+            _ => self.format_in(to_string, seen),
+        }
+    }
 }
@@ -1,34 +1,271 @@
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
 use crate::bytecodes::Bytecodes;
+use crate::function::{Closure, Function, NativeFn, NativeFunction, Upvalue, UpvalueRef};
+use crate::compiler;
+use crate::files::FileAccess;
+use crate::natives;
+use crate::object::{
+    BoundMethod, BuiltinMethod, Class, Generator, GeneratorRef, GeneratorState, Instance, Iter, Map, Members, Module,
+    Range, SuspendedHandler, Task, TaskRef, Variant,
+};
+use crate::opcode::{OpCode, FINALLY_JUMP, FINALLY_RETURN, FINALLY_THROW};
+use crate::scheduler::{Clock, Scheduler};
+use crate::value::{Value, ValueRepr, ValueResult};
+
+/// Default limit of nested calls, see [`VM::set_call_depth_limit`].
+pub const FRAMES_MAX: usize = 1024;
+/// Default limit of values on the stack, see [`VM::set_stack_limit`].
+pub const STACK_MAX: usize = FRAMES_MAX * 256;
+// limit of methods called from inside an instruction running at once, each one nests `run` on the host stack
+const NESTED_MAX: usize = 64;
+
+/// Invocation of a closure, `slots` is the stack index of the callee and its locals follow it.
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    slots: usize,
+    // generator running in this frame, it is suspended again by `yield`
+    generator: Option<GeneratorRef>,
+    // caller `ip` leaving the `for-in` loop that resumed the generator, once it finishes
+    loop_exit: Option<usize>,
+    // resumed by `done()`, the generator's next value is kept back and whether it has one returned
+    peeking: bool,
+    // task whose body runs in this frame, the scheduler switches to another task when it awaits
+    task: Option<TaskRef>,
+    // parameters the call left out whose defaults still have to run before the body, in order
+    missing: Vec<u8>,
+}
 
-use crate::compiler::Compiler;
-use crate::debug::debug_bytecode;
-use crate::opcode::OpCode;
-use crate::value::{Value, ValueRepr};
+impl CallFrame {
+    fn new(closure: Rc<Closure>, slots: usize) -> Self {
+        Self {
+            closure,
+            ip: 0,
+            slots,
+            generator: None,
+            loop_exit: None,
+            peeking: false,
+            task: None,
+            missing: Vec::new(),
+        }
+    }
+}
 
-pub const STACK_MAX: usize = 256;
+/// Active `try` block, a thrown value unwinds to `frames` and `stack_top` and resumes at `catch_ip`.
+/// Returning through it runs the `finally` block at `finally_ip` first.
+struct Handler {
+    frames: usize,
+    stack_top: usize,
+    catch_ip: usize,
+    finally_ip: Option<usize>,
+}
 
 pub struct VM {
-    code: Bytecodes,
-    // instruction pointer
+    frames: Vec<CallFrame>,
+    // instruction pointer of the innermost frame
     ip: usize,
     // stack pointer
     stack_top: usize,
     stack: Vec<Value>,
 
-    // global variable store
+    // natives, every module sees them besides its own globals
     globals: HashMap<String, Value>,
+    // module of the script being run, and of the REPL
+    main: Rc<Module>,
+    // modules imported so far by canonical path, a module is added before its code runs
+    modules: HashMap<PathBuf, Rc<Module>>,
+    // files whose code is running, the importing one before the one it imports
+    importing: Vec<PathBuf>,
+    // directories searched for imports not found next to the importing file
+    library_paths: Vec<PathBuf>,
+    // files the file natives may read and write
+    file_access: FileAccess,
+    // where `print` writes
+    output: Box<dyn Write>,
+
+    // upvalues still pointing at live stack slots, ordered by slot
+    open_upvalues: Vec<UpvalueRef>,
+
+    // innermost try block last
+    handlers: Vec<Handler>,
+    // class of the error objects built-in runtime errors are thrown as
+    error_class: Rc<Class>,
+
+    scheduler: Scheduler,
+    // task top-level code is blocked on in `await`, tasks run on top of its frame meanwhile
+    main_wait: Option<TaskRef>,
+
+    stack_max: usize,
+    frames_max: usize,
+    // what is wrong with the bytecode of the running instruction, raised once the instruction is done
+    fault: Cell<Option<&'static str>>,
+    // what `peek` hands out when the stack has nothing to peek at
+    nil: Value,
+    // methods running inside `invoke`
+    nested: usize,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum InterpretResult {
     InterpretOk,
     InterpretCompileError,
     InterpretRuntimeError,
 }
 
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl VM {
     pub fn new() -> VM {
-        Self { code: Default::default(), ip: 0, stack: Vec::new(), stack_top: 0, globals: HashMap::new() }
+        let mut vm = Self {
+            frames: Vec::new(),
+            ip: 0,
+            stack: Vec::new(),
+            stack_top: 0,
+            globals: HashMap::new(),
+            main: Rc::new(Module::new("main", None)),
+            modules: HashMap::new(),
+            importing: Vec::new(),
+            library_paths: Vec::new(),
+            file_access: FileAccess::None,
+            output: Box::new(io::stdout()),
+            open_upvalues: Vec::new(),
+            handlers: Vec::new(),
+            error_class: Rc::new(Class::new("Error")),
+            scheduler: Scheduler::new(Clock::Real),
+            main_wait: None,
+            stack_max: STACK_MAX,
+            frames_max: FRAMES_MAX,
+            fault: Cell::new(None),
+            nil: Value::new(),
+            nested: 0,
+        };
+
+        vm.define_native("int", 1, natives::int);
+        vm.define_native("float", 1, natives::float);
+        vm.define_native("str", 1, natives::str);
+        vm.define_native("len", 1, natives::len);
+        vm.define_native("push", 2, natives::push);
+        vm.define_native("divmod", 2, natives::divmod);
+        vm.define_native("implements", 2, natives::implements);
+        vm.define_native("sleep", 1, natives::sleep);
+        vm.define_native("spawn", 1, natives::spawn);
+        vm.define_native("now", 0, natives::now);
+
+        vm.define_native("sqrt", 1, natives::sqrt);
+        vm.define_native("pow", 2, natives::pow);
+        vm.define_native("abs", 1, natives::abs);
+        vm.define_native("floor", 1, natives::floor);
+        vm.define_native("ceil", 1, natives::ceil);
+        vm.define_native("round", 1, natives::round);
+        vm.define_native("min", 2, natives::min);
+        vm.define_native("max", 2, natives::max);
+        vm.define_native("clamp", 3, natives::clamp);
+        vm.define_native("sin", 1, natives::sin);
+        vm.define_native("cos", 1, natives::cos);
+        vm.define_native("tan", 1, natives::tan);
+        vm.define_native("asin", 1, natives::asin);
+        vm.define_native("acos", 1, natives::acos);
+        vm.define_native("atan", 1, natives::atan);
+        vm.define_native("atan2", 2, natives::atan2);
+        vm.define_native("sinh", 1, natives::sinh);
+        vm.define_native("cosh", 1, natives::cosh);
+        vm.define_native("tanh", 1, natives::tanh);
+        vm.define_native("asinh", 1, natives::asinh);
+        vm.define_native("acosh", 1, natives::acosh);
+        vm.define_native("atanh", 1, natives::atanh);
+        vm.define_native("log", 1, natives::log);
+        vm.define_native("log2", 1, natives::log2);
+        vm.define_native("log10", 1, natives::log10);
+        vm.define_native("exp", 1, natives::exp);
+        vm.define_native("is_nan", 1, natives::is_nan);
+        vm.define_native("gcd", 2, natives::gcd);
+        vm.define_native("lcm", 2, natives::lcm);
+        vm.define_global("pi", Value(ValueRepr::Number(std::f64::consts::PI)));
+        vm.define_global("e", Value(ValueRepr::Number(std::f64::consts::E)));
+        vm.define_global("inf", Value(ValueRepr::Number(f64::INFINITY)));
+        vm.define_global("nan", Value(ValueRepr::Number(f64::NAN)));
+
+        vm.define_native("read_file", 1, natives::read_file);
+        vm.define_native("write_file", 2, natives::write_file);
+        vm.define_native("append_file", 2, natives::append_file);
+        vm.define_native("read_lines", 1, natives::read_lines);
+        vm.define_native("exists", 1, natives::exists);
+        vm.define_native("remove", 1, natives::remove);
+        vm.define_native("list_dir", 1, natives::list_dir);
+        vm.define_native("mkdir", 1, natives::mkdir);
+
+        vm
+    }
+
+    /// Selects the clock `sleep` timers run on, see [`Clock`].
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.scheduler.set_clock(clock);
+    }
+
+    pub fn now(&self) -> u64 {
+        self.scheduler.now()
+    }
+
+    pub fn spawn(&mut self, task: &TaskRef) {
+        self.scheduler.start(task);
+    }
+
+    /// Limits how many values the stack holds, going over raises a "Stack overflow." error.
+    pub fn set_stack_limit(&mut self, values: usize) {
+        self.stack_max = values;
+    }
+
+    /// Limits how deep calls nest, going over raises a "Stack overflow." error.
+    pub fn set_call_depth_limit(&mut self, frames: usize) {
+        self.frames_max = frames;
+    }
+
+    pub fn define_native(&mut self, name: &str, arity: u8, function: NativeFn) {
+        let native = NativeFunction::new(name, arity, function);
+        self.define_global(name, Value(ValueRepr::Native(Rc::new(native))));
+    }
+
+    /// Defines a global every module sees, a module's own global of the same name hides it.
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    /// Tells the VM which file the script it runs comes from, paths it imports are relative to it.
+    pub fn set_script_path(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.main = Rc::new(Module::new(&module_name(&path), Some(path.clone())));
+        self.modules.insert(path.clone(), self.main.clone());
+        self.importing = vec![path];
+    }
+
+    /// Decides which files scripts may read and write, they may touch none until it is set.
+    pub fn set_file_access(&mut self, access: FileAccess) {
+        self.file_access = access;
+    }
+
+    pub fn file_access(&self) -> &FileAccess {
+        &self.file_access
+    }
+
+    /// Sends what `print` writes to `output` instead of the standard output.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Adds a directory searched for the modules a file imports when they aren't next to it.
+    /// Directories are searched in the order they are added.
+    pub fn add_library_path(&mut self, dir: &Path) {
+        self.library_paths.push(dir.to_path_buf());
     }
 
     fn reset(&mut self) {
@@ -36,47 +273,104 @@ impl VM {
     }
 
     pub fn interpret(&mut self, code: Bytecodes) -> InterpretResult {
-        self.code = code;
+        let function = Rc::new(Function::new("script", 0, 0, code));
+        let closure = Rc::new(Closure::new(function, Vec::new(), self.main.clone()));
+
+        self.push(Value(ValueRepr::Closure(closure.clone())));
+        self.frames.push(CallFrame::new(closure, self.stack_top - 1));
+
         self.reset();
         self.run()
     }
 
     fn run(&mut self) -> InterpretResult {
         loop {
-            if self.is_end() {
-                return InterpretResult::InterpretRuntimeError;
+            if let Some(e) = self.step() {
+                return e;
             }
+        }
+    }
 
-            let op: OpCode = self.read_opcode();
+    /// Runs the next instruction, raising the malformed bytecode or stack overflow it ran into.
+    fn step(&mut self) -> Option<InterpretResult> {
+        let op: OpCode = self.read_opcode();
 
-            // self.print_stack(&op, "BEFORE");
-            match self.process(&op) {
-                None => {}
-                Some(e) => return e,
-            }
-            // self.print_stack(&op, "AFTER");
+        // self.print_stack(&op, "BEFORE");
+        let result = self.process(&op);
+        // self.print_stack(&op, "AFTER");
+
+        if let Some(fault) = self.fault.take() {
+            return self.runtime_error(&format!("Malformed bytecode: {}", fault));
         }
+        let overflow = self.frames.len() > self.frames_max || self.stack_top > self.stack_max;
+        if result.is_none() && (overflow || self.nested > NESTED_MAX) {
+            return self.runtime_error("Stack overflow.");
+        }
+        result
     }
 
     fn process(&mut self, op: &OpCode) -> Option<InterpretResult> {
         match op {
             OpCode::OpLoop => {
                 let offset = self.read_short() as usize;
-                self.ip -= offset;
+                match self.ip.checked_sub(offset) {
+                    Some(ip) => self.ip = ip,
+                    None => self.fault("jump out of bounds."),
+                }
             }
             OpCode::OpSetLocal => {
-                let slot = self.read_byte();
-                let val = self.peek(0);
-                self.stack[slot as usize] = val.clone();
+                let slot = self.slots() + self.read_byte() as usize;
+                let val = self.peek(0).clone();
+                self.set_slot(slot, val);
             }
             OpCode::OpGetLocal => {
-                let slot = self.read_byte();
-                let val: &Value = self.stack.get(slot as usize).unwrap();
-                self.push(val.clone());
+                let slot = self.slots() + self.read_byte() as usize;
+                let val = self.slot(slot);
+                self.push(val);
+            }
+            OpCode::OpGetUpvalue => {
+                let idx = self.read_byte() as usize;
+                let upvalue = self.upvalue(idx);
+                let val = match &*upvalue.borrow() {
+                    Upvalue::Open(slot) => self.slot(*slot),
+                    Upvalue::Closed(val) => val.clone(),
+                };
+                self.push(val);
+            }
+            OpCode::OpSetUpvalue => {
+                let idx = self.read_byte() as usize;
+                let upvalue = self.upvalue(idx);
+                let val = self.peek(0).clone();
+                let mut upvalue = upvalue.borrow_mut();
+                match &mut *upvalue {
+                    Upvalue::Open(slot) => self.set_slot(*slot, val),
+                    Upvalue::Closed(closed) => *closed = val,
+                }
+            }
+            OpCode::OpClosure => {
+                let function = match self.read_const().0 {
+                    ValueRepr::Function(function) => function,
+                    _ => return self.runtime_error("Closure operand must be a function."),
+                };
+
+                let mut upvalues = Vec::with_capacity(function.upvalue_count as usize);
+                for _ in 0..function.upvalue_count {
+                    let is_local = self.read_byte() == 1;
+                    let index = self.read_byte() as usize;
+                    let upvalue = match is_local {
+                        true => self.capture_upvalue(self.slots() + index),
+                        false => self.upvalue(index),
+                    };
+                    upvalues.push(upvalue);
+                }
+
+                let closure = Closure::new(function, upvalues, self.module().clone());
+                self.push(Value(ValueRepr::Closure(Rc::new(closure))));
             }
             OpCode::OpPopN => {
                 let idx = self.read_byte();
-                self.pop_n(idx);
+                self.close_upvalues(self.stack_top.saturating_sub(idx as usize));
+                self.pop_n(idx as usize);
             }
             OpCode::OpJump => {
                 let offset = self.read_short() as usize;
@@ -89,70 +383,127 @@ impl VM {
                     self.ip += offset
                 }
             }
+            OpCode::OpJumpIfNil => {
+                let offset = self.read_short() as usize;
+                if let ValueRepr::Nil() = self.peek(0).0 {
+                    self.ip += offset
+                }
+            }
             OpCode::OpSetGlobal => {
                 let key = self.read_const_str();
-                match self.globals.contains_key(&key) {
+                let module = self.module().clone();
+                // assigning to a native's name gives the module its own global hiding the native
+                let defined = module.globals.borrow().contains_key(&key) || self.globals.contains_key(&key);
+                match defined {
                     false => {
-                        eprintln!("Undefined variable '{}'.", &key);
-                        return Some(InterpretResult::InterpretRuntimeError);
+                        return self.runtime_error(&format!("Undefined variable '{}'.", &key));
                     }
                     true => {
-                        self.globals.insert(key.into(), self.peek(0).clone());
+                        module.globals.borrow_mut().insert(key, self.peek(0).clone());
                     }
                 }
             }
             OpCode::OpGetGlobal => {
                 let key = self.read_const_str();
-                match self.globals.get(&key) {
+                let value = self.module().globals.borrow().get(&key).cloned();
+                match value.or_else(|| self.globals.get(&key).cloned()) {
                     None => {
-                        eprintln!("Undefined variable '{}'.", &key);
-                        return Some(InterpretResult::InterpretRuntimeError);
+                        return self.runtime_error(&format!("Undefined variable '{}'.", &key));
                     }
-                    Some(v) => self.push(v.clone().into()),
+                    Some(v) => self.push(v),
                 }
             }
             OpCode::OpDefineGlobal => {
                 let key = self.read_const_str();
                 let val = self.peek(0).clone();
-                self.globals.insert(key, val);
-                self.pop_n(2);
+                self.module().globals.borrow_mut().insert(key, val);
+                self.pop();
             }
             OpCode::OpPop => {
                 self.pop();
             }
             OpCode::OpPrint => {
-                let _ = &self.pop().print();
+                let value = self.pop();
+                let text = match self.stringify(&value) {
+                    Ok(text) => text,
+                    Err(error) => return self.throw(error),
+                };
+                if let Err(e) = writeln!(self.output, "{}", text) {
+                    return self.runtime_error(&format!("Could not print: {}.", e));
+                }
             }
             OpCode::OpReturn => {
-                return Some(InterpretResult::InterpretOk);
+                let result = self.pop();
+                return self.return_value(result);
             }
             OpCode::OpConstant => {
                 let constant = self.read_const();
                 self.push(constant)
             }
             OpCode::OpNegate => {
-                let val = -self.pop();
-                self.push(val)
+                let value = self.pop();
+                if let Some(method) = self.operator_method(&value, "__neg__") {
+                    return self.call_operator(value, method, Vec::new());
+                }
+                return self.push_result(-value);
             }
-            OpCode::OpAdd => {
-                let r = self.pop();
-                let l = self.pop();
-                self.push(l + r)
+            OpCode::OpAdd => return self.binary_op("__add__", "__radd__", |l, r| l + r),
+            OpCode::OpSubtract => return self.binary_op("__sub__", "__rsub__", |l, r| l - r),
+            OpCode::OpMultiple => return self.binary_op("__mul__", "__rmul__", |l, r| l * r),
+            OpCode::OpDivide => return self.binary_op("__div__", "__rdiv__", |l, r| l / r),
+            OpCode::OpFloorDivide => return self.binary_op("__floordiv__", "__rfloordiv__", Value::floor_div),
+            OpCode::OpModulo => return self.binary_op("__mod__", "__rmod__", |l, r| l % r),
+            OpCode::OpPower => return self.binary_op("__pow__", "__rpow__", Value::pow),
+            OpCode::OpBitAnd => return self.binary_op("__and__", "__rand__", |l, r| l & r),
+            OpCode::OpBitOr => return self.binary_op("__or__", "__ror__", |l, r| l | r),
+            OpCode::OpBitXor => return self.binary_op("__xor__", "__rxor__", |l, r| l ^ r),
+            OpCode::OpShiftLeft => return self.binary_op("__lshift__", "__rlshift__", |l, r| l << r),
+            OpCode::OpShiftRight => return self.binary_op("__rshift__", "__rrshift__", |l, r| l >> r),
+            OpCode::OpBitNot => {
+                let value = self.pop();
+                if let Some(method) = self.operator_method(&value, "__invert__") {
+                    return self.call_operator(value, method, Vec::new());
+                }
+                return self.push_result(!value);
             }
-            OpCode::OpSubtract => {
-                let r = self.pop();
-                let l = self.pop();
-                self.push(l - r)
+            OpCode::OpCall => {
+                let argc = self.read_byte() as usize;
+                return self.call_value(argc, &[]);
             }
-            OpCode::OpMultiple => {
-                let r = self.pop();
-                let l = self.pop();
-                self.push(l * r)
+            OpCode::OpTailCall => {
+                let argc = self.read_byte() as usize;
+                return self.tail_call(argc);
             }
-            OpCode::OpDivide => {
-                let r = self.pop();
-                let l = self.pop();
-                self.push(l / r)
+            OpCode::OpCallNamed => {
+                let argc = self.read_byte() as usize;
+                let names: Vec<String> = match self.read_const().0 {
+                    ValueRepr::Tuple(names) => names.iter().map(|name| name.to_string()).collect(),
+                    _ => Vec::new(),
+                };
+                return self.call_value(argc, &names);
+            }
+            OpCode::OpSpread => {
+                let iterable = self.pop();
+                let kind = iterable.0.kind();
+                let items = match self.collect_items(iterable) {
+                    Ok(Some(items)) => items,
+                    Ok(None) => return self.runtime_error(&format!("Cannot spread {}.", kind)),
+                    Err(error) => return self.throw(error),
+                };
+                if let ValueRepr::List(args) = &self.peek(0).0 {
+                    args.borrow_mut().extend(items);
+                }
+            }
+            OpCode::OpCallSpread => {
+                let args = match self.pop().0 {
+                    ValueRepr::List(args) => args.borrow().clone(),
+                    _ => Vec::new(),
+                };
+                let argc = args.len();
+                for arg in args {
+                    self.push(arg);
+                }
+                return self.call_value(argc, &[]);
             }
             OpCode::OpNil => self.push(Value(ValueRepr::Nil())),
             OpCode::OpFalse => self.push(Value(ValueRepr::Boolean(false))),
@@ -161,57 +512,1447 @@ impl VM {
                 let value = self.pop();
                 self.push(Value(ValueRepr::Boolean(self.is_falsey(&value))))
             }
-            OpCode::OpEqual => {
-                let r = self.pop();
-                let l = self.pop();
-                self.push(Value(ValueRepr::Boolean(l == r)))
+            OpCode::OpEqual => return self.equality(),
+            OpCode::OpLess => return self.comparison("__lt__", "__gt__", |l, r| l < r),
+            OpCode::OpGreater => return self.comparison("__gt__", "__lt__", |l, r| l > r),
+            OpCode::OpBuildList => {
+                let count = self.read_byte() as usize;
+                let items = self.take_n(count);
+                self.push(Value::list(items));
+            }
+            OpCode::OpBuildMap => {
+                let count = self.read_byte() as usize;
+                let items = self.take_n(count * 2);
+
+                let mut map = Map::new();
+                for pair in items.chunks_exact(2) {
+                    if let Err(e) = map.set(pair[0].clone(), pair[1].clone()) {
+                        return self.runtime_error(&e);
+                    }
+                }
+                self.push(Value::map(map));
             }
-            OpCode::OpLess => {
-                let r = self.pop();
-                let l = self.pop();
-                self.push(Value(ValueRepr::Boolean(l < r)))
+            OpCode::OpIndexGet => {
+                let index = self.pop();
+                let target = self.pop();
+                if let Some(method) = self.operator_method(&target, "__index__") {
+                    return self.call_operator(target, method, vec![index]);
+                }
+                return self.push_result(target.get_index(&index));
             }
-            OpCode::OpGreater => {
-                let r = self.pop();
-                let l = self.pop();
-                self.push(Value(ValueRepr::Boolean(l > r)))
+            OpCode::OpIndexSet => {
+                let value = self.pop();
+                let index = self.pop();
+                let target = self.pop();
+                if let Err(e) = target.set_index(index, value.clone()) {
+                    return self.runtime_error(&e);
+                }
+                self.push(value);
+            }
+            OpCode::OpRange => {
+                let flags = self.read_byte();
+                let step = match flags & 2 {
+                    0 => Value(ValueRepr::Integer(1)),
+                    _ => self.pop(),
+                };
+                let end = self.pop();
+                let start = self.pop();
+
+                let range = match (start.0, end.0, step.0) {
+                    (ValueRepr::Integer(start), ValueRepr::Integer(end), ValueRepr::Integer(step)) => {
+                        Range::new(start, end, step, flags & 1 == 1)
+                    }
+                    _ => Err("Range bounds and step must be integers.".to_string()),
+                };
+                match range {
+                    Ok(range) => self.push(Value(ValueRepr::Range(range))),
+                    Err(e) => return self.runtime_error(&e),
+                }
+            }
+            OpCode::OpInRange => {
+                let value = self.pop();
+                let range = self.pop();
+                self.push(Value(ValueRepr::Boolean(range.range_contains(&value))))
             }
-            OpCode::OpUnKnown => {
-                eprintln!("unknown opcode")
+            OpCode::OpBuildTuple => {
+                let count = self.read_byte() as usize;
+                let items = self.take_n(count);
+                self.push(Value::tuple(items));
             }
+            OpCode::OpUnpack | OpCode::OpUnpackReversed => {
+                let count = self.read_byte() as usize;
+                let value = self.pop();
+                let kind = value.0.kind();
+                let mut items = match value.0 {
+                    ValueRepr::Tuple(items) => items.to_vec(),
+                    _ => match self.collect_items(value) {
+                        Ok(Some(items)) => items,
+                        Ok(None) => return self.runtime_error(&format!("Cannot unpack {}.", kind)),
+                        Err(error) => return self.throw(error),
+                    },
+                };
+
+                if items.len() != count {
+                    let msg = format!("Expected {} values to unpack but got {}.", count, items.len());
+                    return self.runtime_error(&msg);
+                }
+                // reversed, the first item ends on top to be stored first
+                if let OpCode::OpUnpackReversed = op {
+                    items.reverse();
+                }
+                for item in items {
+                    self.push(item);
+                }
+            }
+            OpCode::OpDup => {
+                let val = self.peek(0).clone();
+                self.push(val);
+            }
+            OpCode::OpDup2 => {
+                let (under, top) = (self.peek(1).clone(), self.peek(0).clone());
+                self.push(under);
+                self.push(top);
+            }
+            OpCode::OpMatchVariant => {
+                let count = self.read_byte() as usize;
+                let subject = self.pop();
+                let pattern = self.pop();
+                let values = match (&subject.0, &pattern.0) {
+                    (ValueRepr::Variant(subject), ValueRepr::Variant(pattern))
+                        if Rc::ptr_eq(&subject.enum_, &pattern.enum_)
+                            && subject.index == pattern.index
+                            && subject.values.len() == count =>
+                    {
+                        Some(subject.values.clone())
+                    }
+                    _ => None,
+                };
+
+                let matched = values.is_some();
+                for value in values.unwrap_or_else(|| vec![Value::new(); count]) {
+                    self.push(value);
+                }
+                self.push(Value(ValueRepr::Boolean(matched)));
+            }
+            OpCode::OpImport => {
+                let path = self.read_const_str();
+                return self.import(&path);
+            }
+            OpCode::OpIterInit => {
+                let mut iterable = self.pop();
+                if let Some(method) = self.operator_method(&iterable, "__iter__") {
+                    iterable = match self.invoke(iterable.clone(), method, Vec::new()) {
+                        Ok(iterator) => iterator,
+                        Err(error) => return self.throw(error),
+                    };
+                }
+                if let ValueRepr::Generator(_) | ValueRepr::Iterator(_) = iterable.0 {
+                    self.push(iterable);
+                    return None;
+                }
+                if self.iterator_methods(&iterable).is_some() {
+                    self.push(iterable);
+                    return None;
+                }
+                match Iter::from_value(&iterable) {
+                    Ok(iter) => self.push(Value(ValueRepr::Iterator(Rc::new(RefCell::new(iter))))),
+                    Err(e) => return self.runtime_error(&e),
+                }
+            }
+            OpCode::OpIterClose => {
+                if let ValueRepr::Generator(generator) = self.pop().0 {
+                    if let Err(error) = self.close_generator(generator) {
+                        return self.throw(error);
+                    }
+                }
+            }
+            OpCode::OpIterNext => {
+                let offset = self.read_short() as usize;
+                let next = match &self.peek(0).0 {
+                    ValueRepr::Iterator(iter) => iter.borrow_mut().try_next(),
+                    ValueRepr::Generator(generator) => {
+                        let generator = generator.clone();
+                        let loop_exit = self.ip + offset;
+                        return self.resume_generator(generator, Some(loop_exit), false);
+                    }
+                    ValueRepr::Instance(_) => {
+                        let iterator = self.peek(0).clone();
+                        return self.instance_next(iterator, offset);
+                    }
+                    _ => Ok(None),
+                };
+                match next {
+                    Ok(Some(value)) => self.push(value),
+                    Ok(None) => self.ip += offset,
+                    Err(e) => return self.runtime_error(&e),
+                }
+            }
+            OpCode::OpClass => {
+                let name = self.read_const_str();
+                self.push(Value(ValueRepr::Class(Rc::new(Class::new(&name)))));
+            }
+            OpCode::OpTrait => {
+                let name = self.read_const_str();
+                self.push(Value(ValueRepr::Class(Rc::new(Class::new_trait(&name)))));
+            }
+            OpCode::OpMixin => {
+                let class = self.pop();
+                let trait_ = self.pop();
+                let mixed = match (&class.0, &trait_.0) {
+                    (ValueRepr::Class(class), ValueRepr::Class(trait_)) if trait_.is_trait => class.mix_in(trait_),
+                    _ => return self.runtime_error(&format!("Can only mix in traits, got {}.", trait_)),
+                };
+                if let Err(e) = mixed {
+                    return self.runtime_error(&e);
+                }
+            }
+            OpCode::OpMethod => {
+                let name = self.read_const_str();
+                let method = self.pop();
+                if let (ValueRepr::Class(class), ValueRepr::Closure(closure)) = (&self.peek(0).0, method.0) {
+                    class.methods.borrow_mut().insert(name, closure);
+                }
+            }
+            OpCode::OpGetter | OpCode::OpSetter | OpCode::OpField => {
+                let name = self.read_const_str();
+                let member = self.pop();
+                if let (ValueRepr::Class(class), ValueRepr::Closure(closure)) = (&self.peek(0).0, member.0) {
+                    match op {
+                        OpCode::OpGetter => class.getters.borrow_mut().insert(name, closure),
+                        OpCode::OpSetter => class.setters.borrow_mut().insert(name, closure),
+                        _ => {
+                            let mut fields = class.fields.borrow_mut();
+                            fields.retain(|(field, _)| *field != name);
+                            fields.push((name, closure));
+                            None
+                        }
+                    };
+                }
+            }
+            OpCode::OpStatic => {
+                let name = self.read_const_str();
+                let member = self.pop();
+                if let ValueRepr::Class(class) = &self.peek(0).0 {
+                    class.statics.borrow_mut().insert(name, member);
+                }
+            }
+            OpCode::OpInherit => {
+                let subclass = self.pop();
+                let superclass = match &self.peek(0).0 {
+                    ValueRepr::Class(superclass) if !superclass.is_trait => superclass.clone(),
+                    _ => return self.runtime_error("Superclass must be a class."),
+                };
+                if let ValueRepr::Class(subclass) = subclass.0 {
+                    subclass.inherit(&superclass);
+                }
+            }
+            OpCode::OpGetProperty => {
+                let name = self.read_const_str();
+                let instance = self.pop();
+                if let Some(getter) = self.accessor(&instance, &name, |class| &class.getters) {
+                    return self.call_operator(instance, getter, Vec::new());
+                }
+                return self.push_result(instance.get_property(&name));
+            }
+            OpCode::OpSetProperty => {
+                let name = self.read_const_str();
+                let value = self.pop();
+                let instance = self.pop();
+                if let Some(setter) = self.accessor(&instance, &name, |class| &class.setters) {
+                    // a setter returns the value it was given
+                    return self.call_operator(instance, setter, vec![value]);
+                }
+                if self.accessor(&instance, &name, |class| &class.getters).is_some() {
+                    return self.runtime_error(&format!("Property '{}' has a getter but no setter.", name));
+                }
+                if let Err(e) = instance.set_property(&name, value.clone()) {
+                    return self.runtime_error(&e);
+                }
+                self.push(value);
+            }
+            OpCode::OpGetSuper => {
+                let name = self.read_const_str();
+                let superclass = self.pop();
+                let receiver = self.pop();
+                let method = match &superclass.0 {
+                    ValueRepr::Class(superclass) => superclass.find_method(&name),
+                    _ => None,
+                };
+                match method {
+                    Some(method) => {
+                        self.push(Value(ValueRepr::BoundMethod(Rc::new(BoundMethod::new(receiver, method)))))
+                    }
+                    None => return self.runtime_error(&format!("Undefined property '{}'.", name)),
+                }
+            }
+            OpCode::OpTry => {
+                let keep = self.read_byte() as usize;
+                let catch_ip = self.read_short() as usize + self.ip;
+                let finally_ip = match self.read_short() as usize {
+                    0 => None,
+                    offset => Some(self.ip + offset),
+                };
+
+                let stack_top = match self.stack_top.checked_sub(keep) {
+                    Some(stack_top) => stack_top,
+                    None => {
+                        self.fault("try block keeps more values than the stack holds.");
+                        return None;
+                    }
+                };
+                self.handlers.push(Handler { frames: self.frames.len(), stack_top, catch_ip, finally_ip });
+            }
+            OpCode::OpPopTry => {
+                self.handlers.pop();
+            }
+            OpCode::OpThrow => {
+                let value = self.pop();
+                return self.throw(value);
+            }
+            OpCode::OpEndFinally => {
+                let kind = self.pop();
+                let value = self.pop();
+                match kind.0 {
+                    ValueRepr::Integer(FINALLY_THROW) => return self.throw(value),
+                    ValueRepr::Integer(FINALLY_RETURN) => return self.return_value(value),
+                    ValueRepr::Integer(FINALLY_JUMP) => {
+                        let target: Vec<usize> = match &value.0 {
+                            ValueRepr::Tuple(items) => items
+                                .iter()
+                                .filter_map(|item| match item.0 {
+                                    ValueRepr::Integer(n) => usize::try_from(n).ok(),
+                                    _ => None,
+                                })
+                                .collect(),
+                            _ => Vec::new(),
+                        };
+                        match target[..] {
+                            [ip, handlers, stack_top] => return self.leave(ip, handlers, stack_top),
+                            _ => self.fault("malformed finally jump."),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            OpCode::OpLeave => {
+                let pops = self.read_byte() as usize;
+                let tries = self.read_byte() as usize;
+                match (self.stack_top.checked_sub(pops), self.handlers.len().checked_sub(tries)) {
+                    (Some(stack_top), Some(handlers)) => return self.leave(self.ip, handlers, stack_top),
+                    _ => self.fault("stack underflow."),
+                }
+            }
+            OpCode::OpYield => {
+                let value = self.pop();
+                return self.suspend_generator(value);
+            }
+            OpCode::OpAwait => {
+                let task = match self.pop().0 {
+                    ValueRepr::Task(task) => task,
+                    // awaiting anything but a task evaluates to the value itself
+                    other => {
+                        self.push(Value(other));
+                        return None;
+                    }
+                };
+                return self.await_task(task);
+            }
+            OpCode::OpUnKnown => self.fault("unknown opcode."),
         };
 
         None
     }
 
+    /// Applies a binary operator, an instance on the left defining `method` implements it itself and
+    /// else one on the right defining `reflected` does, so `2 * v` calls `v.__rmul__(2)`.
+    fn binary_op(
+        &mut self,
+        method: &str,
+        reflected: &str,
+        op: fn(Value, Value) -> ValueResult,
+    ) -> Option<InterpretResult> {
+        let r = self.pop();
+        let l = self.pop();
+        if let Some(method) = self.operator_method(&l, method) {
+            return self.call_operator(l, method, vec![r]);
+        }
+        match self.operator_method(&r, reflected) {
+            Some(method) => self.call_operator(r, method, vec![l]),
+            None => self.push_result(op(l, r)),
+        }
+    }
+
+    /// `==` calls `__eq__` only when both operands are instances, an instance is never equal to a
+    /// value of another kind, so `v == nil` is false without asking `v`.
+    fn equality(&mut self) -> Option<InterpretResult> {
+        if let (ValueRepr::Instance(_), ValueRepr::Instance(_)) = (&self.peek(1).0, &self.peek(0).0) {
+            return self.comparison("__eq__", "__eq__", |l, r| l == r);
+        }
+        let r = self.pop();
+        let l = self.pop();
+        self.push(Value(ValueRepr::Boolean(l == r)));
+        None
+    }
+
+    /// Compares with the left operand's `method`, or else the right operand's `reflected` one, so
+    /// `a < b` tries `a.__lt__(b)` and then `b.__gt__(a)`.
+    fn comparison(&mut self, method: &str, reflected: &str, op: fn(&Value, &Value) -> bool) -> Option<InterpretResult> {
+        let r = self.pop();
+        let l = self.pop();
+        if let Some(method) = self.operator_method(&l, method) {
+            return self.call_operator(l, method, vec![r]);
+        }
+        if let Some(method) = self.operator_method(&r, reflected) {
+            return self.call_operator(r, method, vec![l]);
+        }
+        self.push(Value(ValueRepr::Boolean(op(&l, &r))));
+        None
+    }
+
+    /// Method of an instance's class implementing an operator, such as `__add__` or `toString`.
+    fn operator_method(&self, value: &Value, name: &str) -> Option<Rc<Closure>> {
+        match &value.0 {
+            ValueRepr::Instance(instance) => instance.class.find_method(name),
+            _ => None,
+        }
+    }
+
+    /// The `done` and `next` methods of an instance iterating like a generator does.
+    fn iterator_methods(&self, value: &Value) -> Option<(Rc<Closure>, Rc<Closure>)> {
+        Some((self.operator_method(value, "done")?, self.operator_method(value, "next")?))
+    }
+
+    /// Advances a for-in loop over an instance, pushing what its `next()` returns or jumping by
+    /// `offset` out of the loop once its `done()` is true.
+    fn instance_next(&mut self, iterator: Value, offset: usize) -> Option<InterpretResult> {
+        let (done, next) = match self.iterator_methods(&iterator) {
+            Some(methods) => methods,
+            None => return self.runtime_error("Iterator must have 'done' and 'next' methods."),
+        };
+        match self.invoke(iterator.clone(), done, Vec::new()) {
+            Ok(done) if !self.is_falsey(&done) => {
+                self.ip += offset;
+                None
+            }
+            Ok(_) => match self.invoke(iterator, next, Vec::new()) {
+                Ok(value) => {
+                    self.push(value);
+                    None
+                }
+                Err(error) => self.throw(error),
+            },
+            Err(error) => self.throw(error),
+        }
+    }
+
+    /// The values a spread or destructuring takes from `iterable`, through the same protocol as a
+    /// `for-in` loop. `None` when it can't be iterated.
+    fn collect_items(&mut self, iterable: Value) -> Result<Option<Vec<Value>>, Value> {
+        let mut iterable = iterable;
+        if let Some(method) = self.operator_method(&iterable, "__iter__") {
+            iterable = self.invoke(iterable.clone(), method, Vec::new())?;
+        }
+
+        let mut items = Vec::new();
+        if let ValueRepr::Generator(generator) = &iterable.0 {
+            // `done()` runs the generator up to its next value and keeps it back
+            loop {
+                let done = self.run_nested(|vm| vm.resume_generator(generator.clone(), None, true))?;
+                if !self.is_falsey(&done) {
+                    break;
+                }
+                items.push(generator.borrow_mut().peeked.take().unwrap_or_default());
+            }
+            generator.borrow_mut().peeked = None;
+            return Ok(Some(items));
+        }
+        if let Some((done, next)) = self.iterator_methods(&iterable) {
+            loop {
+                let finished = self.invoke(iterable.clone(), done.clone(), Vec::new())?;
+                if !self.is_falsey(&finished) {
+                    break;
+                }
+                items.push(self.invoke(iterable.clone(), next.clone(), Vec::new())?);
+            }
+            return Ok(Some(items));
+        }
+        Ok(Iter::from_value(&iterable).ok().map(|iter| iter.collect()))
+    }
+
+    /// Finishes a generator a `for-in` loop left early, running the `finally` blocks it is suspended
+    /// in as if it returned at its `yield`.
+    fn close_generator(&mut self, generator: GeneratorRef) -> Result<(), Value> {
+        let mut suspended = generator.borrow_mut();
+        suspended.peeked = None;
+        if suspended.state != GeneratorState::Suspended {
+            return Ok(());
+        }
+        if suspended.handlers.iter().all(|handler| handler.finally_ip.is_none()) {
+            suspended.state = GeneratorState::Done;
+            suspended.stack.clear();
+            return Ok(());
+        }
+        drop(suspended);
+
+        self.run_nested(|vm| match vm.resume_generator(generator.clone(), None, false) {
+            None => vm.return_value(Value::new()),
+            result => result,
+        })?;
+        let mut closed = generator.borrow_mut();
+        if closed.state == GeneratorState::Done {
+            return Ok(());
+        }
+        closed.state = GeneratorState::Done;
+        closed.stack.clear();
+        drop(closed);
+        Err(self.error_value("Generator can't yield while it closes."))
+    }
+
+    /// Getter or setter `name` of an instance's class, from the `accessors` of the class. Fields of
+    /// the instance by the same name hide it.
+    fn accessor(&self, value: &Value, name: &str, accessors: fn(&Class) -> &Members) -> Option<Rc<Closure>> {
+        match &value.0 {
+            ValueRepr::Instance(instance) if !instance.fields.borrow().contains_key(name) => {
+                accessors(&instance.class).borrow().get(name).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    /// Calls an operator method with `receiver` as `this`, what it returns is the operation's result.
+    fn call_operator(&mut self, receiver: Value, method: Rc<Closure>, args: Vec<Value>) -> Option<InterpretResult> {
+        let argc = args.len();
+        self.push(receiver);
+        for arg in args {
+            self.push(arg);
+        }
+        self.call_closure(method, argc, &[])
+    }
+
+    /// Calls a method and runs it to completion within the current instruction, for operations like
+    /// `print` that need its result before they can go on. What the method throws is returned.
+    fn invoke(&mut self, receiver: Value, method: Rc<Closure>, args: Vec<Value>) -> Result<Value, Value> {
+        self.run_nested(|vm| vm.call_operator(receiver, method, args))
+    }
+
+    /// Runs the frame `start` pushes until it returns what it leaves on the stack, within the
+    /// current instruction. What the frame throws is returned.
+    fn run_nested(&mut self, start: impl FnOnce(&mut Self) -> Option<InterpretResult>) -> Result<Value, Value> {
+        let (frames, stack_top, ip) = (self.frames.len(), self.stack_top, self.ip);
+        // catches everything the frame throws, no code lives at `catch_ip`
+        self.handlers.push(Handler { frames, stack_top, catch_ip: usize::MAX, finally_ip: None });
+
+        self.nested += 1;
+        let mut result = start(self);
+        while result.is_none() && self.frames.len() > frames {
+            result = self.step();
+        }
+        self.nested -= 1;
+
+        if result.is_some() {
+            // the program ended while the method ran
+            return Err(Value::new());
+        }
+        let value = self.pop();
+        match self.ip {
+            usize::MAX => {
+                self.ip = ip;
+                Err(value)
+            }
+            _ => {
+                self.handlers.pop();
+                Ok(value)
+            }
+        }
+    }
+
+    /// Formats `value` for `print`, instances with a `toString` method are shown as what it returns.
+    pub fn stringify(&mut self, value: &Value) -> Result<String, Value> {
+        let mut error = None;
+        let text = value.format(&mut |value| {
+            let method = self.operator_method(value, "toString")?;
+            if error.is_some() {
+                return None;
+            }
+            match self.invoke(value.clone(), method, Vec::new()) {
+                Ok(text) => Some(text.to_string()),
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            }
+        });
+        match error {
+            Some(error) => Err(error),
+            None => Ok(text),
+        }
+    }
+
+    fn push_result(&mut self, result: ValueResult) -> Option<InterpretResult> {
+        match result {
+            Ok(v) => {
+                self.push(v);
+                None
+            }
+            Err(e) => self.runtime_error(&e),
+        }
+    }
+
+    /// Calls the value below the `argc` arguments on the stack, the last `names.len()` arguments are
+    /// passed by name.
+    fn call_value(&mut self, argc: usize, names: &[String]) -> Option<InterpretResult> {
+        if argc >= self.stack_top || names.len() > argc {
+            self.fault("call has more arguments than the stack holds.");
+            return None;
+        }
+        let callee = self.peek(argc).clone();
+        match callee.0 {
+            ValueRepr::Closure(closure) if closure.function.is_async || closure.function.is_generator => {
+                let missing = match self.bind_arguments(&closure.function, argc, names) {
+                    Ok(missing) => missing,
+                    Err(e) => return self.runtime_error(&e),
+                };
+
+                let arity = closure.function.arity as usize;
+                let stack = self.take_n(arity + 1);
+                let value = match closure.function.is_async {
+                    true => ValueRepr::Task(Rc::new(RefCell::new(Task::new(closure, stack, missing)))),
+                    false => ValueRepr::Generator(Rc::new(RefCell::new(Generator::new(closure, stack, missing)))),
+                };
+                self.push(Value(value));
+                None
+            }
+            ValueRepr::Closure(closure) => self.call_closure(closure, argc, names),
+            ValueRepr::BoundMethod(bound) => {
+                self.set_slot(self.stack_top - argc - 1, bound.receiver.clone());
+                self.call_closure(bound.method.clone(), argc, names)
+            }
+            ValueRepr::Class(class) if class.is_trait => {
+                self.runtime_error(&format!("Can't instantiate trait {}.", class.name))
+            }
+            ValueRepr::Class(class) => {
+                let instance = Rc::new(Instance::new(class.clone()));
+                self.set_slot(self.stack_top - argc - 1, Value(ValueRepr::Instance(instance.clone())));
+
+                let fields = class.fields.borrow().clone();
+                for (name, initializer) in fields {
+                    match self.invoke(Value(ValueRepr::Instance(instance.clone())), initializer, Vec::new()) {
+                        Ok(value) => instance.fields.borrow_mut().insert(name, value),
+                        Err(error) => return self.throw(error),
+                    };
+                }
+
+                match class.find_method("init") {
+                    Some(init) => self.call_closure(init, argc, names),
+                    None if argc != 0 => self.runtime_error(&format!("Expected 0 arguments but got {}.", argc)),
+                    None => None,
+                }
+            }
+            _ if !names.is_empty() => self.runtime_error(&format!("{} doesn't take named arguments.", callee)),
+            ValueRepr::BuiltinMethod(method) => self.call_builtin_method(&method, argc),
+            ValueRepr::Variant(variant) if variant.is_constructor() => {
+                let arity = variant.decl().fields.len();
+                if argc != arity {
+                    return self.runtime_error(&format!("Expected {} arguments but got {}.", arity, argc));
+                }
+
+                let values = self.take_n(argc);
+                self.pop();
+                let value = Variant::new(variant.enum_.clone(), variant.index, values);
+                self.push(Value(ValueRepr::Variant(Rc::new(value))));
+                None
+            }
+            ValueRepr::Native(native) => {
+                if argc != native.arity as usize {
+                    return self.runtime_error(&format!("Expected {} arguments but got {}.", native.arity, argc));
+                }
+
+                let args = self.stack[self.stack_top - argc..].to_vec();
+                let result = (native.function)(self, &args);
+                self.pop_n(argc + 1);
+                self.push_result(result)
+            }
+            _ => self.runtime_error("Can only call functions and classes."),
+        }
+    }
+
+    fn call_closure(&mut self, closure: Rc<Closure>, argc: usize, names: &[String]) -> Option<InterpretResult> {
+        let missing = match self.bind_arguments(&closure.function, argc, names) {
+            Ok(missing) => missing,
+            Err(e) => return self.runtime_error(&e),
+        };
+
+        let arity = closure.function.arity as usize;
+        self.frames.last_mut().unwrap().ip = self.ip;
+        let mut frame = CallFrame::new(closure, self.stack_top - arity - 1);
+        frame.missing = missing;
+        self.frames.push(frame);
+        self.ip = 0;
+        self.fill_defaults()
+    }
+
+    /// Calls a closure in place of the current frame, whose locals are dropped for the callee and
+    /// its arguments. Other callees are called normally, the `OpReturn` after the call returns
+    /// their result.
+    fn tail_call(&mut self, argc: usize) -> Option<InterpretResult> {
+        if argc >= self.stack_top {
+            self.fault("call has more arguments than the stack holds.");
+            return None;
+        }
+        let closure = match &self.peek(argc).0 {
+            ValueRepr::Closure(closure) if !closure.function.is_async && !closure.function.is_generator => {
+                closure.clone()
+            }
+            ValueRepr::BoundMethod(bound) => {
+                let (method, receiver) = (bound.method.clone(), bound.receiver.clone());
+                self.set_slot(self.stack_top - argc - 1, receiver);
+                method
+            }
+            _ => return self.call_value(argc, &[]),
+        };
+
+        let slots = self.slots();
+        self.close_upvalues(slots);
+        let call = self.take_n(argc + 1);
+        self.truncate_stack(slots);
+        for value in call {
+            self.push(value);
+        }
+
+        let missing = match self.bind_arguments(&closure.function, argc, &[]) {
+            Ok(missing) => missing,
+            Err(e) => return self.runtime_error(&e),
+        };
+        let frame = self.frames.last_mut().unwrap();
+        (frame.closure, frame.missing) = (closure, missing);
+        self.ip = 0;
+        self.fill_defaults()
+    }
+
+    /// Rearranges the `argc` arguments on top of the stack into `function`'s parameter order, with a
+    /// `nil` placeholder for each parameter left out. Returns the left out parameters, which all have
+    /// a default.
+    fn bind_arguments(&mut self, function: &Function, argc: usize, names: &[String]) -> Result<Vec<u8>, String> {
+        let arity = function.arity as usize;
+        let rest = function.has_rest();
+        // parameters taking one positional argument each
+        let fixed = arity - rest as usize;
+        let positional = argc - names.len();
+        if names.is_empty() && argc == arity && !rest {
+            return Ok(Vec::new());
+        }
+        if (positional > fixed && !rest) || (names.is_empty() && argc < function.required()) {
+            return Err(match function.required() {
+                required if rest => format!("Expected at least {} arguments but got {}.", required, argc),
+                required if required == arity => format!("Expected {} arguments but got {}.", arity, argc),
+                required => format!("Expected {} to {} arguments but got {}.", required, arity, argc),
+            });
+        }
+
+        let args = self.take_n(argc);
+        let mut bound: Vec<Option<Value>> = vec![None; arity];
+        for (slot, arg) in args.iter().take(positional.min(fixed)).enumerate() {
+            bound[slot] = Some(arg.clone());
+        }
+        for (name, arg) in names.iter().zip(&args[positional..]) {
+            let slot = match function.params.iter().position(|param| &param.name == name) {
+                Some(slot) => slot,
+                None => return Err(format!("Unknown argument '{}'.", name)),
+            };
+            if bound[slot].is_some() {
+                return Err(format!("Duplicate argument '{}'.", name));
+            }
+            bound[slot] = Some(arg.clone());
+        }
+        if rest {
+            let extra = args[positional.min(fixed)..positional].to_vec();
+            match bound[fixed] {
+                Some(_) if !extra.is_empty() => {
+                    return Err(format!("Duplicate argument '{}'.", function.params[fixed].name));
+                }
+                Some(_) => {}
+                None => bound[fixed] = Some(Value::list(extra)),
+            }
+        }
+
+        let mut missing = Vec::new();
+        for (slot, arg) in bound.into_iter().enumerate() {
+            match arg {
+                Some(arg) => self.push(arg),
+                None if function.params[slot].default.is_some() => {
+                    self.push(Value::new());
+                    missing.push(slot as u8);
+                }
+                None => return Err(format!("Missing argument '{}'.", function.params[slot].name)),
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Runs the default of the next parameter the current frame's call left out, `return_value`
+    /// stores the result in the parameter's slot and comes back here for the one after.
+    fn fill_defaults(&mut self) -> Option<InterpretResult> {
+        let frame = self.frames.last().unwrap();
+        let param = match frame.missing.first() {
+            Some(param) => *param as usize,
+            None => return None,
+        };
+
+        let (slots, enclosing) = (frame.slots, frame.closure.clone());
+        let default = enclosing.function.params[param].default.as_ref().unwrap();
+        let upvalues = default
+            .upvalues
+            .iter()
+            .map(|upvalue| match upvalue.is_local {
+                true => self.capture_upvalue(slots + upvalue.index as usize),
+                false => enclosing.upvalues[upvalue.index as usize].clone(),
+            })
+            .collect();
+
+        let closure = Rc::new(Closure::new(default.function.clone(), upvalues, enclosing.module.clone()));
+        self.push(Value(ValueRepr::Closure(closure.clone())));
+        self.call_closure(closure, 0, &[])
+    }
+
+    /// Pushes the module at `path`, running its code the first time it is imported. The path is
+    /// relative to the directory of the importing file.
+    fn import(&mut self, path: &str) -> Option<InterpretResult> {
+        let file = match self.resolve_module(path) {
+            Some(file) => file,
+            None => return self.runtime_error(&format!("Can't find module '{}'.", path)),
+        };
+        if let Some(start) = self.importing.iter().position(|importing| *importing == file) {
+            let mut cycle: Vec<String> = self.importing[start..].iter().map(|file| module_name(file)).collect();
+            cycle.push(module_name(&file));
+            return self.runtime_error(&format!("Circular import: {}.", cycle.join(" -> ")));
+        }
+        if let Some(module) = self.modules.get(&file) {
+            self.push(Value(ValueRepr::Module(module.clone())));
+            return None;
+        }
+
+        let code = match fs::read_to_string(&file) {
+            Ok(source) => compiler::compile(source),
+            Err(e) => return self.runtime_error(&format!("Can't read module '{}': {}.", path, e)),
+        };
+        let code = match code {
+            Some(code) => code,
+            None => return self.runtime_error(&format!("Could not compile module '{}'.", path)),
+        };
+
+        let module = Rc::new(Module::new(&module_name(&file), Some(file.clone())));
+        let function = Rc::new(Function::new("script", 0, 0, code));
+        let closure = Rc::new(Closure::new(function, Vec::new(), module.clone()));
+        self.modules.insert(file.clone(), module.clone());
+        self.importing.push(file.clone());
+        let result = self.invoke(Value(ValueRepr::Closure(closure.clone())), closure, Vec::new());
+        self.importing.pop();
+
+        match result {
+            Ok(_) => {
+                self.push(Value(ValueRepr::Module(module)));
+                None
+            }
+            Err(error) => {
+                // a module whose code failed is run again by the next import of it
+                self.modules.remove(&file);
+                self.throw(error)
+            }
+        }
+    }
+
+    /// Finds the file an `import` of `path` refers to, by its canonical path. It is looked up next
+    /// to the importing file and then in the library paths.
+    fn resolve_module(&self, path: &str) -> Option<PathBuf> {
+        let base = match &self.module().path {
+            Some(file) => file.parent().map(Path::to_path_buf).unwrap_or_default(),
+            None => PathBuf::from("."),
+        };
+        std::iter::once(&base)
+            .chain(self.library_paths.iter())
+            .find_map(|dir| dir.join(path).canonicalize().ok())
+    }
+
+    fn call_builtin_method(&mut self, method: &BuiltinMethod, argc: usize) -> Option<InterpretResult> {
+        if argc != 0 {
+            return self.runtime_error(&format!("Expected 0 arguments but got {}.", argc));
+        }
+
+        let generator = match &method.receiver.0 {
+            ValueRepr::Generator(generator) => generator.clone(),
+            ValueRepr::Task(task) => {
+                let done = task.borrow().outcome.is_some();
+                self.pop();
+                self.push(Value(ValueRepr::Boolean(done)));
+                return None;
+            }
+            _ => return self.runtime_error(&format!("Undefined method '{}'.", method.name)),
+        };
+        self.pop();
+
+        let peek = method.name != "next";
+        self.resume_generator(generator, None, peek)
+    }
+
+    /// Pushes the generator's saved frame back onto the stack and continues it. A `for-in` loop passes
+    /// where to continue once the generator is done, `next()` gets `nil` instead. `done()` peeks, it
+    /// runs the generator up to its next yielded or returned value and keeps that back for `next()`,
+    /// so the side effects of the body up to there happen when `done()` is called.
+    fn resume_generator(
+        &mut self,
+        generator: GeneratorRef,
+        loop_exit: Option<usize>,
+        peek: bool,
+    ) -> Option<InterpretResult> {
+        let mut suspended = generator.borrow_mut();
+        if suspended.peeked.is_some() {
+            let done = suspended.state == GeneratorState::Done;
+            match loop_exit {
+                _ if peek => self.push(Value(ValueRepr::Boolean(done))),
+                // a `for-in` loop leaves out the returned value
+                Some(exit) if done => {
+                    suspended.peeked = None;
+                    self.ip = exit;
+                }
+                _ => self.push(suspended.peeked.take().unwrap()),
+            }
+            return None;
+        }
+        match suspended.state {
+            GeneratorState::Running => {
+                drop(suspended);
+                return self.runtime_error("Generator is already running.");
+            }
+            GeneratorState::Done => {
+                match loop_exit {
+                    Some(exit) => self.ip = exit,
+                    None if peek => self.push(Value(ValueRepr::Boolean(true))),
+                    None => self.push(Value::new()),
+                }
+                return None;
+            }
+            GeneratorState::Suspended => suspended.state = GeneratorState::Running,
+        }
+
+        self.frames.last_mut().unwrap().ip = self.ip;
+        let slots = self.restore_frame(&mut suspended);
+        let closure = suspended.closure.clone();
+        let missing = std::mem::take(&mut suspended.missing);
+        drop(suspended);
+
+        let mut frame = CallFrame::new(closure, slots);
+        (frame.ip, frame.generator, frame.loop_exit, frame.peeking) = (self.ip, Some(generator), loop_exit, peek);
+        frame.missing = missing;
+        self.frames.push(frame);
+        self.fill_defaults()
+    }
+
+    /// Saves the generator frame running `yield` and hands `value` to whoever resumed it.
+    fn suspend_generator(&mut self, value: Value) -> Option<InterpretResult> {
+        let generator = match self.frames.last().and_then(|frame| frame.generator.clone()) {
+            Some(generator) => generator,
+            None => return self.runtime_error("Can only yield from a generator."),
+        };
+        let frame = self.frames.pop().unwrap();
+
+        let mut suspended = generator.borrow_mut();
+        self.save_frame(frame.slots, &mut suspended);
+        suspended.state = GeneratorState::Suspended;
+
+        self.ip = self.frames.last().unwrap().ip;
+        if frame.peeking {
+            suspended.peeked = Some(value);
+            self.push(Value(ValueRepr::Boolean(false)));
+        } else {
+            self.push(value);
+        }
+        None
+    }
+
+    /// Moves the stack segment from `slots`, the `try` blocks and the `ip` of the frame just popped
+    /// into `saved`. Upvalues into the segment are closed meanwhile and remembered to reopen them.
+    fn save_frame(&mut self, slots: usize, saved: &mut Generator) {
+        saved.upvalues = self
+            .open_upvalues
+            .iter()
+            .filter_map(|upvalue| match *upvalue.borrow() {
+                Upvalue::Open(slot) if slot >= slots => Some((slot - slots, upvalue.clone())),
+                _ => None,
+            })
+            .collect();
+        self.close_upvalues(slots);
+        let mut handlers = Vec::new();
+        while self.handlers.last().is_some_and(|h| h.frames > self.frames.len()) {
+            let handler = self.handlers.pop().unwrap();
+            let stack_offset = handler.stack_top.saturating_sub(slots);
+            let (catch_ip, finally_ip) = (handler.catch_ip, handler.finally_ip);
+            handlers.insert(0, SuspendedHandler { stack_offset, catch_ip, finally_ip });
+        }
+
+        saved.stack = self.take_n(self.stack_top.saturating_sub(slots));
+        saved.handlers = handlers;
+        saved.ip = self.ip;
+    }
+
+    /// Puts a frame saved by `save_frame` back on top of the stack and returns its slots, the
+    /// caller pushes the frame itself.
+    fn restore_frame(&mut self, saved: &mut Generator) -> usize {
+        let slots = self.stack_top;
+        for value in std::mem::take(&mut saved.stack) {
+            self.push(value);
+        }
+        for (offset, upvalue) in std::mem::take(&mut saved.upvalues) {
+            let slot = slots + offset;
+            let closed = std::mem::replace(&mut *upvalue.borrow_mut(), Upvalue::Open(slot));
+            if let Upvalue::Closed(value) = closed {
+                self.stack[slot] = value;
+            }
+            self.open_upvalues.push(upvalue);
+        }
+        for handler in std::mem::take(&mut saved.handlers) {
+            self.handlers.push(Handler {
+                frames: self.frames.len() + 1,
+                stack_top: slots + handler.stack_offset,
+                catch_ip: handler.catch_ip,
+                finally_ip: handler.finally_ip,
+            });
+        }
+
+        self.ip = saved.ip;
+        slots
+    }
+
+    /// Waits for `task`, starting it if needed. A task awaiting it is suspended until it completes,
+    /// top-level code keeps the scheduler running other tasks in the meantime.
+    fn await_task(&mut self, task: TaskRef) -> Option<InterpretResult> {
+        if task.borrow().outcome.is_some() {
+            return self.settle(task);
+        }
+
+        self.scheduler.start(&task);
+        match self.frames.last().unwrap().task.clone() {
+            Some(current) if Rc::ptr_eq(&current, &task) => return self.runtime_error("A task can't await itself."),
+            Some(current) => {
+                task.borrow_mut().waiters.push(current.clone());
+                let frame = self.frames.pop().unwrap();
+                let mut suspended = current.borrow_mut();
+                suspended.awaiting = Some(task);
+                let coroutine = suspended.coroutine.as_mut().unwrap();
+                self.save_frame(frame.slots, coroutine);
+                coroutine.state = GeneratorState::Suspended;
+            }
+            None => {
+                self.frames.last_mut().unwrap().ip = self.ip;
+                self.main_wait = Some(task);
+            }
+        }
+
+        self.run_scheduler()
+    }
+
+    /// Pushes the value a completed task returned, or throws what it threw.
+    fn settle(&mut self, task: TaskRef) -> Option<InterpretResult> {
+        self.scheduler.unobserved.retain(|failed| !Rc::ptr_eq(failed, &task));
+        let outcome = task.borrow().outcome.clone().unwrap();
+        match outcome {
+            Ok(value) => {
+                self.push(value);
+                None
+            }
+            Err(error) => self.throw(error),
+        }
+    }
+
+    /// Switches to the next ready task, or back to top-level code once the task it awaits is done.
+    /// Without an awaited task it runs until no task is left to run.
+    fn run_scheduler(&mut self) -> Option<InterpretResult> {
+        if let Some(task) = self.main_wait.clone() {
+            if task.borrow().outcome.is_some() {
+                self.main_wait = None;
+                self.ip = self.frames.last().unwrap().ip;
+                return self.settle(task);
+            }
+        }
+
+        match self.scheduler.next_task() {
+            Some(task) => self.resume_task(task),
+            None => {
+                self.ip = self.frames.last().unwrap().ip;
+                match self.main_wait.take() {
+                    // a timer that fired while looking for a ready task has no waiter to wake
+                    Some(task) if task.borrow().outcome.is_some() => self.settle(task),
+                    Some(_) => self.runtime_error("Deadlock, the awaited task can never complete."),
+                    None => None,
+                }
+            }
+        }
+    }
+
+    /// Continues a ready task on top of the stack, handing it the outcome of the task it awaited.
+    fn resume_task(&mut self, task: TaskRef) -> Option<InterpretResult> {
+        let mut running = task.borrow_mut();
+        let awaited = running.awaiting.take();
+        let coroutine = running.coroutine.as_mut().unwrap();
+        coroutine.state = GeneratorState::Running;
+        let slots = self.restore_frame(coroutine);
+        let closure = coroutine.closure.clone();
+        let missing = std::mem::take(&mut coroutine.missing);
+        drop(running);
+
+        let mut frame = CallFrame::new(closure, slots);
+        (frame.ip, frame.task, frame.missing) = (self.ip, Some(task), missing);
+        self.frames.push(frame);
+        match awaited {
+            Some(awaited) => self.settle(awaited),
+            None => self.fill_defaults(),
+        }
+    }
+
+    /// Fails the task running in frame `index` with `error`, dropping its frames, and switches to
+    /// the next task.
+    fn fail_task(&mut self, index: usize, error: Value) -> Option<InterpretResult> {
+        let slots = self.frames[index].slots;
+        let task = self.frames[index].task.clone().unwrap();
+        for frame in self.frames.drain(index..) {
+            if let Some(generator) = frame.generator {
+                generator.borrow_mut().state = GeneratorState::Done;
+            }
+        }
+        self.close_upvalues(slots);
+        self.truncate_stack(slots);
+
+        if let Some(coroutine) = task.borrow_mut().coroutine.as_mut() {
+            coroutine.state = GeneratorState::Done;
+        }
+        self.scheduler.complete(&task, Err(error));
+        self.run_scheduler()
+    }
+
+    /// Leaves a loop through `break` or `continue`, popping the handlers above `handlers` and the stack
+    /// down to `stack_top` before resuming at `ip`. Each finally block of a popped handler runs first.
+    fn leave(&mut self, ip: usize, handlers: usize, stack_top: usize) -> Option<InterpretResult> {
+        while self.handlers.len() > handlers {
+            let handler = self.handlers.pop().unwrap();
+            if let Some(finally_ip) = handler.finally_ip {
+                self.close_upvalues(handler.stack_top);
+                self.truncate_stack(handler.stack_top);
+                let target = [ip, handlers, stack_top].map(|n| Value(ValueRepr::Integer(n as i64)));
+                self.push(Value::tuple(target.to_vec()));
+                self.push(Value(ValueRepr::Integer(FINALLY_JUMP)));
+                self.ip = finally_ip;
+                return None;
+            }
+        }
+
+        self.close_upvalues(stack_top);
+        self.truncate_stack(stack_top);
+        self.ip = ip;
+        None
+    }
+
+    /// Returns `result` from the current frame, running the frame's pending `finally` blocks first.
+    fn return_value(&mut self, mut result: Value) -> Option<InterpretResult> {
+        while self.handlers.last().is_some_and(|h| h.frames == self.frames.len()) {
+            let handler = self.handlers.pop().unwrap();
+            if let Some(finally_ip) = handler.finally_ip {
+                self.close_upvalues(handler.stack_top);
+                self.truncate_stack(handler.stack_top);
+                self.push(result);
+                self.push(Value(ValueRepr::Integer(FINALLY_RETURN)));
+                self.ip = finally_ip;
+                return None;
+            }
+        }
+
+        if self.frames.len() == 1 && !self.scheduler.is_idle() {
+            // top-level code is done, run the spawned tasks before its final `OpReturn` runs again
+            self.push(result);
+            self.frames[0].ip = self.ip - 1;
+            return self.run_scheduler();
+        }
+
+        let frame = self.frames.pop().unwrap();
+        self.close_upvalues(frame.slots);
+        self.truncate_stack(frame.slots);
+
+        if let Some(task) = frame.task {
+            if let Some(coroutine) = task.borrow_mut().coroutine.as_mut() {
+                coroutine.state = GeneratorState::Done;
+            }
+            self.scheduler.complete(&task, Ok(result));
+            return self.run_scheduler();
+        }
+        match self.frames.last() {
+            None => return self.report_unobserved(),
+            Some(caller) => self.ip = caller.ip,
+        }
+        if let Some(generator) = frame.generator {
+            generator.borrow_mut().state = GeneratorState::Done;
+            if let Some(exit) = frame.loop_exit {
+                self.ip = exit;
+                return None;
+            }
+            if frame.peeking {
+                generator.borrow_mut().peeked = Some(result);
+                result = Value(ValueRepr::Boolean(true));
+            }
+        }
+
+        let caller = self.frames.last_mut().unwrap();
+        if !caller.missing.is_empty() {
+            let slot = caller.slots + 1 + caller.missing.remove(0) as usize;
+            self.set_slot(slot, result);
+            return self.fill_defaults();
+        }
+        self.push(result);
+        None
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> UpvalueRef {
+        let existing = self.open_upvalues.iter().find(|u| matches!(*u.borrow(), Upvalue::Open(s) if s == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Moves the values of upvalues pointing at `last` or above off the stack.
+    fn close_upvalues(&mut self, last: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= last => {
+                    *upvalue = Upvalue::Closed(stack.get(slot).cloned().unwrap_or_default());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    /// Raises a built-in error as an `Error` instance with `message` and `trace` fields.
+    fn runtime_error(&mut self, msg: &str) -> Option<InterpretResult> {
+        let error = self.error_value(msg);
+        self.throw(error)
+    }
+
+    /// `Error` instance with `message` and the current `trace`.
+    fn error_value(&self, msg: &str) -> Value {
+        let error = Instance::new(self.error_class.clone());
+        {
+            let mut fields = error.fields.borrow_mut();
+            fields.insert("message".to_string(), Value(ValueRepr::String(msg.to_string())));
+            fields.insert("trace".to_string(), Value::list(self.trace()));
+        }
+        Value(ValueRepr::Instance(Rc::new(error)))
+    }
+
+    /// Unwinds to the innermost `try` block and pushes `value` for its `catch`, or aborts the
+    /// program when nothing catches it.
+    fn throw(&mut self, value: Value) -> Option<InterpretResult> {
+        // a task's frame is the bottom of its call stack, a throw nothing inside the task catches fails it
+        if let Some(index) = self.frames.iter().rposition(|frame| frame.task.is_some()) {
+            if self.handlers.last().is_none_or(|handler| handler.frames <= index) {
+                return self.fail_task(index, value);
+            }
+        }
+
+        let handler = match self.handlers.pop() {
+            Some(handler) => handler,
+            None => return self.uncaught(value),
+        };
+
+        // generators unwound through can't be resumed anymore
+        for frame in self.frames.drain(handler.frames..) {
+            if let Some(generator) = frame.generator {
+                generator.borrow_mut().state = GeneratorState::Done;
+            }
+        }
+        self.close_upvalues(handler.stack_top);
+        self.truncate_stack(handler.stack_top);
+        self.push(value);
+        self.ip = handler.catch_ip;
+        None
+    }
+
+    /// Ends the program, reporting the error of a task that failed without anyone awaiting it.
+    fn report_unobserved(&mut self) -> Option<InterpretResult> {
+        let unobserved = std::mem::take(&mut self.scheduler.unobserved);
+        let error = match unobserved.first() {
+            Some(task) => task.borrow().outcome.clone().and_then(Result::err).unwrap_or_default(),
+            None => return Some(InterpretResult::InterpretOk),
+        };
+        self.uncaught(error)
+    }
+
+    /// Where each frame is, innermost first, e.g. `[line 3] in area()`.
+    /// Lines of the call stack innermost first, runs of the same line are collapsed into one.
+    fn trace(&self) -> Vec<Value> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut repeated = 0;
+        for (depth, frame) in self.frames.iter().rev().enumerate() {
+            // the innermost frame's ip is live, callers saved theirs when calling
+            let ip = if depth == 0 { self.ip } else { frame.ip };
+            let function = &frame.closure.function;
+            let line = format!("[line {}] in {}()", function.bytecodes.line_at(ip.saturating_sub(1)), function.name);
+            if lines.last() == Some(&line) {
+                repeated += 1;
+                continue;
+            }
+            if repeated > 0 {
+                lines.push(format!("[previous line repeated {} more times]", repeated));
+                repeated = 0;
+            }
+            lines.push(line);
+        }
+        if repeated > 0 {
+            lines.push(format!("[previous line repeated {} more times]", repeated));
+        }
+        lines.into_iter().map(|line| Value(ValueRepr::String(line))).collect()
+    }
+
+    fn uncaught(&mut self, value: Value) -> Option<InterpretResult> {
+        let is_error = matches!(&value.0, ValueRepr::Instance(i) if Rc::ptr_eq(&i.class, &self.error_class));
+        let (message, trace) = match is_error {
+            true => {
+                let field = |name| value.get_property(name).unwrap_or_default();
+                (field("message"), field("trace"))
+            }
+            false => (Value(ValueRepr::String(format!("Uncaught {}", value))), Value::list(self.trace())),
+        };
+
+        eprintln!("{}", message);
+        if let ValueRepr::List(trace) = &trace.0 {
+            for line in trace.borrow().iter() {
+                eprintln!("{}", line);
+            }
+        }
+
+        self.stack.clear();
+        self.stack_top = 0;
+        self.frames.clear();
+        self.fault.set(None);
+        self.open_upvalues.clear();
+        self.handlers.clear();
+        Some(InterpretResult::InterpretRuntimeError)
+    }
+
+    fn slots(&self) -> usize {
+        self.frames.last().unwrap().slots
+    }
+
+    /// Module the running code was defined in, its globals are the ones it reads and defines.
+    fn module(&self) -> &Rc<Module> {
+        &self.frames.last().unwrap().closure.module
+    }
+
+    fn bytecodes(&self) -> &Bytecodes {
+        &self.frames.last().unwrap().closure.function.bytecodes
+    }
+
+    /// Records that the running instruction found its bytecode malformed, `run` raises it as a
+    /// runtime error once the instruction is done. Accessors hand out `nil` or nothing meanwhile.
+    fn fault(&self, msg: &'static str) {
+        if self.fault.get().is_none() {
+            self.fault.set(Some(msg));
+        }
+    }
+
     fn push(&mut self, value: Value) {
         self.stack_top += 1;
         self.stack.push(value);
     }
 
     fn pop(&mut self) -> Value {
-        self.stack_top -= 1;
-        self.stack.pop().unwrap()
+        match self.stack.pop() {
+            Some(value) => {
+                self.stack_top -= 1;
+                value
+            }
+            None => {
+                self.fault("stack underflow.");
+                Value::new()
+            }
+        }
+    }
+
+    fn pop_n(&mut self, idx: usize) {
+        self.take_n(idx);
+    }
+
+    /// Pops the top `count` values, bottom one first.
+    fn take_n(&mut self, count: usize) -> Vec<Value> {
+        if count > self.stack_top {
+            self.fault("stack underflow.");
+        }
+        self.stack_top -= count.min(self.stack_top);
+        self.stack.split_off(self.stack_top)
     }
 
-    fn pop_n(&mut self, idx: u8) {
-        self.stack_top -= idx as usize;
-        self.stack.drain(self.stack_top..);
+    /// Pops values until `stack_top` is back at `to`.
+    fn truncate_stack(&mut self, to: usize) {
+        match self.stack_top.checked_sub(to) {
+            Some(count) => self.pop_n(count),
+            None => self.fault("stack underflow."),
+        }
     }
 
     fn peek(&self, distance: usize) -> &Value {
-        self.stack.get(self.stack_top - 1 - distance).unwrap()
+        let slot = self.stack_top.checked_sub(distance + 1);
+        match slot.and_then(|slot| self.stack.get(slot)) {
+            Some(value) => value,
+            None => {
+                self.fault("stack underflow.");
+                &self.nil
+            }
+        }
+    }
+
+    fn slot(&self, slot: usize) -> Value {
+        match self.stack.get(slot) {
+            Some(value) => value.clone(),
+            None => {
+                self.fault("local slot out of bounds.");
+                Value::new()
+            }
+        }
+    }
+
+    fn set_slot(&mut self, slot: usize, value: Value) {
+        match self.stack.get_mut(slot) {
+            Some(current) => *current = value,
+            None => self.fault("local slot out of bounds."),
+        }
+    }
+
+    fn upvalue(&self, idx: usize) -> UpvalueRef {
+        match self.frames.last().and_then(|frame| frame.closure.upvalues.get(idx)) {
+            Some(upvalue) => upvalue.clone(),
+            None => {
+                self.fault("upvalue index out of bounds.");
+                Rc::new(RefCell::new(Upvalue::Closed(Value::new())))
+            }
+        }
     }
 
     fn peek_byte(&mut self, pos: usize) -> u8 {
-        let instr = self.code.code.get(self.ip + pos).unwrap();
-        return instr.clone();
+        match self.bytecodes().code.get(self.ip + pos) {
+            Some(byte) => *byte,
+            None => {
+                self.fault("bytecode ended unexpectedly.");
+                OpCode::OpUnKnown.into()
+            }
+        }
     }
 
     fn read_byte(&mut self) -> u8 {
-        let instr = self.code.code.get(self.ip).unwrap();
+        let instr = self.peek_byte(0);
         self.ip += 1;
-        return instr.clone();
+        instr
     }
 
     fn read_short(&mut self) -> u16 {
@@ -229,7 +1970,13 @@ impl VM {
 
     fn read_const(&mut self) -> Value {
         let idx = self.read_byte();
-        self.code.values.get(idx as usize).unwrap().clone()
+        match self.bytecodes().values.get(idx as usize) {
+            Some(constant) => constant.clone(),
+            None => {
+                self.fault("constant index out of bounds.");
+                Value::new()
+            }
+        }
     }
 
     fn read_const_str(&mut self) -> String {
@@ -253,9 +2000,10 @@ impl VM {
     }
 
     pub fn is_end(&self) -> bool {
-        self.ip >= self.code.code.len()
+        self.ip >= self.bytecodes().code.len()
     }
 
+    #[allow(dead_code)]
     fn print_stack(&self, op: &OpCode, action: &str) {
         eprintln!("OP: {} {}", &op, action);
         for (k, v) in self.stack.iter().enumerate() {
@@ -264,3 +2012,79 @@ impl VM {
         eprintln!()
     }
 }
+
+/// Name a module of the file at `path` goes by, the file name without its extension.
+fn module_name(path: &Path) -> String {
+    path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::compiler::compile;
+
+    fn run(code: &[u8], constants: Vec<Value>) -> InterpretResult {
+        let mut bytecodes = Bytecodes::new();
+        for byte in code {
+            bytecodes.write(*byte, 1);
+        }
+        for constant in constants {
+            bytecodes.add_const(constant);
+        }
+        VM::new().interpret(bytecodes)
+    }
+
+    #[test]
+    fn truncated_code_is_a_runtime_error() {
+        let number = vec![Value(ValueRepr::Number(1.0))];
+        let result = run(&[OpCode::OpConstant.into()], number.clone());
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+        let result = run(&[OpCode::OpJump.into(), 0], Vec::new());
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+        let result = run(&[OpCode::OpConstant.into(), 0], number);
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+    }
+
+    #[test]
+    fn bad_constant_index_is_a_runtime_error() {
+        let result = run(&[OpCode::OpConstant.into(), 3, OpCode::OpReturn.into()], Vec::new());
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+        let result = run(&[OpCode::OpGetGlobal.into(), 0, OpCode::OpReturn.into()], Vec::new());
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+    }
+
+    #[test]
+    fn argc_past_the_stack_is_a_runtime_error() {
+        let result = run(&[OpCode::OpCall.into(), 9, OpCode::OpReturn.into()], Vec::new());
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+        let result = run(&[OpCode::OpTailCall.into(), 200, OpCode::OpReturn.into()], Vec::new());
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+        let result = run(&[OpCode::OpPopN.into(), 5, OpCode::OpReturn.into()], Vec::new());
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+    }
+
+    #[test]
+    fn unknown_opcode_is_a_runtime_error() {
+        let result = run(&[250, OpCode::OpReturn.into()], Vec::new());
+        assert!(matches!(result, InterpretResult::InterpretRuntimeError));
+    }
+
+    #[test]
+    fn stack_overflow_trace_collapses_repeated_frames() {
+        let source = "fun r() { r(); } var trace; try { r(); } catch (e) { trace = e.trace; }";
+        let mut vm = VM::new();
+        let result = vm.interpret(compile(source.to_string()).unwrap());
+        assert!(matches!(result, InterpretResult::InterpretOk));
+
+        let trace = vm.main.globals.borrow().get("trace").cloned().unwrap();
+        let lines: Vec<String> = match &trace.0 {
+            ValueRepr::List(lines) => lines.borrow().iter().map(|line| line.to_string()).collect(),
+            _ => panic!("trace is not a list"),
+        };
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "[line 1] in r()");
+        assert!(lines[1].starts_with("[previous line repeated "));
+        assert_eq!(lines[2], "[line 1] in script()");
+    }
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{compiles, run};
+
+#[test]
+fn break_and_continue_leave_the_innermost_loop() {
+    let source = r#"
+        var i = 0;
+        while (true) {
+            i = i + 1;
+            if (i == 5) break;
+            if (i % 2 == 0) continue;
+            print i;
+        }
+        for (var j = 0; j < 6; j = j + 1) {
+            var skipped = j * 10;
+            if (j == 2) continue;
+            if (j == 4) break;
+            print skipped;
+        }
+    "#;
+    assert_eq!(run(source), ["1", "3", "0", "10", "30"]);
+}
+
+#[test]
+fn labels_target_outer_loops() {
+    let source = r#"
+        outer: for (x in 0..3) {
+            for (y in 0..3) {
+                if (y == 1) continue outer;
+                if (x == 2) break outer;
+                print x + y;
+            }
+        }
+        rows: while (true) {
+            while (true) break rows;
+        }
+        print "done";
+    "#;
+    assert_eq!(run(source), ["0", "1", "done"]);
+}
+
+#[test]
+fn break_keeps_the_stack_balanced() {
+    let source = r#"
+        var total = 0;
+        for (x in 0..100) {
+            var a = x; var b = x;
+            if (x == 50) break;
+            total = total + a + b;
+        }
+        var after = "still here";
+        print total; print after;
+    "#;
+    assert_eq!(run(source), ["2450", "still here"]);
+}
+
+#[test]
+fn jumps_outside_a_loop_or_to_unknown_labels_do_not_compile() {
+    assert!(!compiles("break;"));
+    assert!(!compiles("fun f() { continue; }"));
+    assert!(!compiles("while (true) { fun f() { break; } }"));
+    assert!(!compiles("while (true) break nowhere;"));
+}
@@ -0,0 +1,64 @@
+//! Runs scripts the way the command line does and hands back what they printed.
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use apoloo::compiler::compile;
+use apoloo::scheduler::Clock;
+use apoloo::vm::{InterpretResult, VM};
+
+/// Shared buffer `print` writes into.
+#[derive(Clone, Default)]
+pub struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+/// VM printing into the returned buffer, with timers on the virtual clock.
+pub fn vm() -> (VM, Output) {
+    let output = Output::default();
+    let mut vm = VM::new();
+    vm.set_clock(Clock::Virtual);
+    vm.set_output(Box::new(output.clone()));
+    (vm, output)
+}
+
+/// Runs `source` on `vm`, `None` when it doesn't compile.
+pub fn interpret(vm: &mut VM, source: &str) -> Option<InterpretResult> {
+    compile(source.to_string()).map(|code| vm.interpret(code))
+}
+
+/// Runs `source`, which must compile and run without an uncaught error, and returns its printed lines.
+pub fn run(source: &str) -> Vec<String> {
+    let (result, lines) = run_result(source);
+    assert_eq!(result, Some(InterpretResult::InterpretOk), "script failed, it printed {:?}", lines);
+    lines
+}
+
+/// Runs `source` and returns how it ended with its printed lines, `None` when it doesn't compile.
+pub fn run_result(source: &str) -> (Option<InterpretResult>, Vec<String>) {
+    let (mut vm, output) = vm();
+    let result = interpret(&mut vm, source);
+    (result, output.text().lines().map(str::to_string).collect())
+}
+
+/// Whether `source` compiles.
+pub fn compiles(source: &str) -> bool {
+    compile(source.to_string()).is_some()
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::{compiles, run};
+
+const SHAPES: &str = r#"
+    enum Color { Red, Green, Blue }
+    enum Shape {
+        Circle(r),
+        Rect(w, h),
+        Empty,
+    }
+"#;
+
+fn run_with_enums(script: &str) -> Vec<String> {
+    run(&format!("{}{}", SHAPES, script))
+}
+
+#[test]
+fn variants_print_and_compare_by_value() {
+    let lines = run_with_enums(
+        "print Color.Red; print Color.Red == Color.Red; print Color.Red == Color.Blue;
+         print [Shape.Circle(2), Shape.Rect(3, 4), Shape.Empty];
+         print Shape.Rect(3, 4) == Shape.Rect(3, 4); print Shape.Rect(3, 4) == Shape.Rect(4, 3);
+         print Shape.Circle(2).r;",
+    );
+    let listed = "[Shape.Circle(2), Shape.Rect(3, 4), Shape.Empty]";
+    assert_eq!(lines, ["Color.Red", "true", "false", listed, "true", "false", "2"]);
+}
+
+#[test]
+fn match_tells_variants_apart_and_binds_payloads() {
+    let lines = run_with_enums(
+        "fun area(shape) {
+             return match (shape) {
+                 Shape.Circle(r) => 3 * r * r,
+                 Shape.Rect(w, h) if w == h => w * w,
+                 Shape.Rect(w, _) => w * shape.h,
+                 Shape.Empty => 0,
+                 _ => nil,
+             };
+         }
+         for (shape in [Shape.Circle(2), Shape.Rect(3, 3), Shape.Rect(3, 4), Shape.Empty, Color.Red]) print area(shape);
+         print match (Color.Green) { Color.Red | Color.Green => \"warm\", _ => \"cold\" };",
+    );
+    assert_eq!(lines, ["12", "9", "12", "0", "NIL", "warm"]);
+}
+
+#[test]
+fn variants_work_as_map_keys() {
+    let lines = run_with_enums(
+        r#"var names = {Color.Red: "red", Shape.Circle(1): "unit circle"};
+           print names[Color.Red]; print names[Shape.Circle(1)];"#,
+    );
+    assert_eq!(lines, ["red", "unit circle"]);
+}
+
+#[test]
+fn misuse_is_reported() {
+    let lines = run_with_enums(
+        "try { Shape.Rect(1); } catch (e) { print e.message; }
+         try { Color.Purple; } catch (e) { print e.message; }",
+    );
+    assert_eq!(lines, ["Expected 2 arguments but got 1.", "Undefined variant 'Purple' of Color."]);
+    assert!(!compiles("enum E { A, A }"));
+}
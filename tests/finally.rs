@@ -0,0 +1,83 @@
+mod common;
+
+use apoloo::vm::InterpretResult;
+use common::{run, run_result};
+
+#[test]
+fn finally_runs_on_break_and_continue() {
+    let source = r#"
+        for (var i = 0; i < 3; i = i + 1) {
+            try {
+                if (i == 0) continue;
+                break;
+            } finally {
+                print "left " + str(i);
+            }
+        }
+        print "after";
+    "#;
+    assert_eq!(run(source), ["left 0", "left 1", "after"]);
+}
+
+#[test]
+fn labelled_break_runs_every_finally_it_leaves() {
+    let source = r#"
+        outer: while (true) {
+            try {
+                while (true) {
+                    try { break outer; } finally { print "inner"; }
+                }
+            } finally {
+                print "outer";
+            }
+        }
+        print "out";
+    "#;
+    assert_eq!(run(source), ["inner", "outer", "out"]);
+}
+
+#[test]
+fn finally_runs_on_return_and_keeps_the_value() {
+    let source = r#"
+        fun f() {
+            try {
+                try { return "value"; } finally { print "first"; }
+            } finally {
+                print "second";
+            }
+        }
+        print f();
+        fun g() {
+            for (x in [1, 2]) {
+                try { return x; } finally { print "leaving"; }
+            }
+        }
+        print g();
+    "#;
+    assert_eq!(run(source), ["first", "second", "value", "leaving", "1"]);
+}
+
+#[test]
+fn finally_rethrows_what_nobody_caught() {
+    let source = r#"
+        try {
+            try { throw "boom"; } finally { print "cleanup"; }
+        } catch (e) {
+            print "caught " + e;
+        }
+    "#;
+    assert_eq!(run(source), ["cleanup", "caught boom"]);
+}
+
+#[test]
+fn runtime_errors_are_error_instances() {
+    let lines = run(r#"try { var total = 1 + "one"; } catch (e) { print e.message; print len(e.trace); }"#);
+    assert_eq!(lines, ["Operands must be two numbers or two strings.", "1"]);
+}
+
+#[test]
+fn uncaught_throws_end_the_script_after_finally() {
+    let (result, lines) = run_result(r#"try { throw "not caught"; } finally { print "cleanup"; } print "unreached";"#);
+    assert_eq!(result, Some(InterpretResult::InterpretRuntimeError));
+    assert_eq!(lines, ["cleanup"]);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::run;
+
+#[test]
+fn ranges_count_with_bounds_and_steps() {
+    let lines = run("for (i in 0..3) print i; for (i in 1..=3) print i; for (i in 10..0 step -3) print i;");
+    assert_eq!(lines, ["0", "1", "2", "1", "2", "3", "10", "7", "4", "1"]);
+    assert_eq!(run("print 0..3; print len(0..10 step 3); print len(5..1);"), ["0..3", "4", "0"]);
+}
+
+#[test]
+fn zero_step_is_a_runtime_error() {
+    let lines = run("try { for (i in 0..3 step 0) print i; } catch (e) { print e.message; }");
+    assert_eq!(lines, ["Range step cannot be zero."]);
+}
+
+#[test]
+fn loops_over_lists_map_keys_and_characters() {
+    let source = r#"
+        for (x in [1, "two"]) print x;
+        var ages = {"ann": 31, "bob": 27};
+        for (name in ages) print name;
+        for (ch in "hé") print ch;
+    "#;
+    assert_eq!(run(source), ["1", "two", "ann", "bob", "h", "é"]);
+}
+
+#[test]
+fn instances_iterate_through_their_protocol() {
+    let source = r#"
+        class Countdown {
+            init(from) { this.left = from; }
+            done() { return this.left == 0; }
+            next() { this.left -= 1; return this.left + 1; }
+        }
+        class Team {
+            init(members) { this.members = members; }
+            __iter__() { return this.members; }
+        }
+        for (n in Countdown(2)) print n;
+        for (who in Team(["ann"])) print who;
+    "#;
+    assert_eq!(run(source), ["2", "1", "ann"]);
+}
+
+#[test]
+fn loop_variable_is_fresh_on_every_iteration() {
+    let source = r#"
+        var closures = [];
+        for (i in 0..3) push(closures, fun () { return i; });
+        for (f in closures) print f();
+    "#;
+    assert_eq!(run(source), ["0", "1", "2"]);
+}
+
+#[test]
+fn collections_containing_themselves_print() {
+    assert_eq!(run("var xs = [1]; push(xs, xs); print xs;"), ["[1, [...]]"]);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::run;
+
+const DESCRIBE: &str = r#"
+    fun describe(value) {
+        return match (value) {
+            0 => "zero",
+            1 | 2 | 3 => "small",
+            4..10 => "medium",
+            -5..=-1 => "negative",
+            "a" | "b" => "letter",
+            true => "yes",
+            nil => "nothing",
+            n if n > 100 => n * 2,
+            _ => "other",
+        };
+    }
+"#;
+
+#[test]
+fn arms_match_literals_alternatives_and_ranges() {
+    let source = format!(
+        "{}{}",
+        DESCRIBE,
+        r#"for (v in [0, 2, 9, 10, -3, "b", true, nil, 250, 2.5]) print describe(v);"#
+    );
+    let lines = run(&source);
+    assert_eq!(lines, ["zero", "small", "medium", "other", "negative", "letter", "yes", "nothing", "500", "other"]);
+}
+
+#[test]
+fn guards_see_the_bound_name() {
+    let source = r#"
+        fun size(n) {
+            return match (n) {
+                x if (x > 9) => "big",
+                x if x > (0) => "small",
+                _ => "none",
+            };
+        }
+        print size(50); print size(5); print size(-1);
+    "#;
+    assert_eq!(run(source), ["big", "small", "none"]);
+}
+
+#[test]
+fn match_is_an_expression_and_defaults_to_nil() {
+    let source = r#"
+        var base = 10;
+        print 1 + match (base * 2) { 20 => base, _ => 0 };
+        print match ("x") { "y" => 1 };
+    "#;
+    assert_eq!(run(source), ["11", "NIL"]);
+}
+
+#[test]
+fn subject_is_evaluated_once() {
+    let source = r#"
+        var calls = 0;
+        fun next() { calls = calls + 1; return calls; }
+        print match (next()) { 2 => "two", 1 => "one", _ => "many" };
+        print calls;
+    "#;
+    assert_eq!(run(source), ["one", "1"]);
+}
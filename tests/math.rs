@@ -0,0 +1,58 @@
+mod common;
+
+use common::run;
+
+#[test]
+fn powers_roots_and_absolute_values_keep_ints_exact() {
+    let lines = run("print sqrt(16); print pow(2, 10); print pow(2, -1); print abs(-3); print abs(-2.5);");
+    assert_eq!(lines, ["4.0", "1024", "0.5", "3", "2.5"]);
+}
+
+#[test]
+fn rounding_gives_ints_with_halves_away_from_zero() {
+    let lines = run("print floor(2.7); print floor(-2.5); print ceil(2.1); print round(2.5); print round(-2.5);");
+    assert_eq!(lines, ["2", "-3", "3", "3", "-3"]);
+}
+
+#[test]
+fn min_max_and_clamp_return_the_argument_they_pick() {
+    let lines = run("print min(3, 1.5); print max(3, 1.5); print clamp(15, 0, 10); print clamp(-1.5, 0, 10);");
+    assert_eq!(lines, ["1.5", "3", "10", "0"]);
+}
+
+#[test]
+fn trigonometry_logs_and_constants() {
+    let lines = run(
+        "print sin(pi / 2); print cos(0); print atan2(1, 1) * 4 == pi; print log(e); print log10(1000);
+         print exp(0); print inf > 1e308; print nan == nan; print is_nan(nan); print is_nan(1);",
+    );
+    assert_eq!(lines, ["1.0", "1.0", "true", "1.0", "3.0", "1.0", "true", "false", "true", "false"]);
+}
+
+#[test]
+fn gcd_and_lcm_work_on_ints() {
+    assert_eq!(run("print gcd(12, -18); print lcm(4, 6); print gcd(0, 0);"), ["6", "12", "0"]);
+}
+
+#[test]
+fn bad_arguments_are_runtime_errors() {
+    let source = r#"
+        try { sqrt(-1); } catch (e) { print e.message; }
+        try { sqrt("four"); } catch (e) { print e.message; }
+        try { gcd(4.0, 2); } catch (e) { print e.message; }
+        try { floor(inf); } catch (e) { print e.message; }
+    "#;
+    let expected = [
+        "Math domain error in sqrt().",
+        "sqrt() expects a number, got string.",
+        "gcd() expects an int, got float.",
+        "Cannot convert inf to int.",
+    ];
+    assert_eq!(run(source), expected);
+}
+
+#[test]
+fn assigning_to_a_native_name_hides_it_in_the_module() {
+    let lines = run("e = 1; print e; pi += 1; print pi > 4; sqrt = (x) => x; print sqrt(9); var abs = 0; print abs;");
+    assert_eq!(lines, ["1", "true", "9", "0"]);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use apoloo::vm::InterpretResult;
+
+/// Fresh directory under the system temp directory holding `files`, each a (relative path, source) pair.
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("apoloo-modules-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, source) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+/// Runs `main.apoloo` of `dir` and returns its printed lines.
+fn run_main(dir: &Path) -> Vec<String> {
+    let (mut vm, output) = common::vm();
+    let main = dir.join("main.apoloo");
+    vm.set_script_path(&main);
+    let result = common::interpret(&mut vm, &fs::read_to_string(&main).unwrap());
+    let lines = output.text().lines().map(str::to_string).collect();
+    assert_eq!(result, Some(InterpretResult::InterpretOk), "script failed, it printed {:?}", lines);
+    lines
+}
+
+#[test]
+fn modules_run_once_and_keep_their_own_globals() {
+    let dir = project(
+        "cache",
+        &[
+            (
+                "main.apoloo",
+                r#"import "lib/counter.apoloo" as counter;
+                   from "lib/counter.apoloo" import bump;
+                   var count = 100;
+                   print bump(); print counter.bump(); print count;
+                   import "lib/counter.apoloo" as again;
+                   print again == counter;"#,
+            ),
+            (
+                "lib/counter.apoloo",
+                r#"print "loading"; var count = 0; fun bump() { count = count + 1; return count; }"#,
+            ),
+        ],
+    );
+    assert_eq!(run_main(&dir), ["loading", "1", "2", "100", "true"]);
+}
+
+#[test]
+fn imports_are_relative_to_the_importing_file() {
+    let dir = project(
+        "relative",
+        &[
+            ("main.apoloo", r#"from "lib/outer.apoloo" import greet; print greet();"#),
+            ("lib/outer.apoloo", r#"from "inner.apoloo" import name; fun greet() { return "hi " + name; }"#),
+            ("lib/inner.apoloo", r#"var name = "inner";"#),
+        ],
+    );
+    assert_eq!(run_main(&dir), ["hi inner"]);
+}
+
+#[test]
+fn circular_imports_name_the_cycle() {
+    let dir = project(
+        "cycle",
+        &[
+            ("main.apoloo", r#"try { import "a.apoloo" as a; } catch (e) { print e.message; }"#),
+            ("a.apoloo", r#"import "b.apoloo" as b;"#),
+            ("b.apoloo", r#"import "a.apoloo" as a;"#),
+        ],
+    );
+    assert_eq!(run_main(&dir), ["Circular import: a -> b -> a."]);
+}
+
+#[test]
+fn missing_modules_and_members_are_runtime_errors() {
+    let dir = project(
+        "missing",
+        &[
+            (
+                "main.apoloo",
+                r#"try { import "nowhere.apoloo" as gone; } catch (e) { print e.message; }
+                   import "lib.apoloo" as lib;
+                   try { print lib.upper; } catch (e) { print e.message; }
+                   try { from "lib.apoloo" import upper; } catch (e) { print e.message; }"#,
+            ),
+            ("lib.apoloo", "var lower = 1;"),
+        ],
+    );
+    let no_member = "Module 'lib' has no member 'upper'.";
+    assert_eq!(run_main(&dir), ["Can't find module 'nowhere.apoloo'.", no_member, no_member]);
+}
+
+#[test]
+fn library_paths_are_searched_after_the_importing_directory() {
+    let dir = project(
+        "library",
+        &[
+            ("main.apoloo", r#"from "shared.apoloo" import where; print where;"#),
+            ("libs/shared.apoloo", r#"var where = "library";"#),
+        ],
+    );
+    let (mut vm, output) = common::vm();
+    let main = dir.join("main.apoloo");
+    vm.set_script_path(&main);
+    vm.add_library_path(&dir.join("libs"));
+    let result = common::interpret(&mut vm, &fs::read_to_string(&main).unwrap());
+    assert_eq!(result, Some(InterpretResult::InterpretOk));
+    assert_eq!(output.text(), "library\n");
+}
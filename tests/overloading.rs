@@ -0,0 +1,63 @@
+mod common;
+
+use common::run;
+
+const VEC: &str = r#"
+    class Vec {
+        init(x, y) { this.x = x; this.y = y; }
+        __add__(other) { return Vec(this.x + other.x, this.y + other.y); }
+        __mul__(k) { return Vec(this.x * k, this.y * k); }
+        __rmul__(k) { return Vec(k * this.x, k * this.y); }
+        __rsub__(k) { return Vec(k - this.x, k - this.y); }
+        __neg__() { return Vec(-this.x, -this.y); }
+        __eq__(other) { return this.x == other.x and this.y == other.y; }
+        __index__(i) { return match (i) { 0 => this.x, 1 => this.y }; }
+        toString() { return "Vec(" + str(this.x) + ", " + str(this.y) + ")"; }
+    }
+"#;
+
+fn run_with_vec(script: &str) -> Vec<String> {
+    run(&format!("{}{}", VEC, script))
+}
+
+#[test]
+fn instances_implement_operators_with_methods() {
+    let lines = run_with_vec(
+        "var a = Vec(1, 2); print a + Vec(3, 4); print a * 3; print -a; print a[1];
+         print [a]; print \"a is \" + str(a);",
+    );
+    assert_eq!(lines, ["Vec(4, 6)", "Vec(3, 6)", "Vec(-1, -2)", "2", "[Vec(1, 2)]", "a is Vec(1, 2)"]);
+}
+
+#[test]
+fn right_operand_reflected_method_is_tried_second() {
+    let lines = run_with_vec("print 2 * Vec(1, 2); print 10 - Vec(1, 2); print Vec(1, 2) * 2;");
+    assert_eq!(lines, ["Vec(2, 4)", "Vec(9, 8)", "Vec(2, 4)"]);
+}
+
+#[test]
+fn operators_without_a_method_raise_the_usual_error() {
+    let lines = run_with_vec("try { print 1 + Vec(1, 2); } catch (e) { print e.message; }");
+    assert_eq!(lines, ["Operands must be numbers."]);
+}
+
+#[test]
+fn eq_is_only_asked_about_other_instances() {
+    let lines = run_with_vec(
+        "var v = Vec(1, 2); print v == Vec(1, 2); print v != Vec(2, 1); print v == nil; print nil == v;
+         print v != 1; print v == \"Vec(1, 2)\";",
+    );
+    assert_eq!(lines, ["true", "true", "false", "false", "true", "false"]);
+}
+
+#[test]
+fn comparisons_fall_back_to_the_reflected_method() {
+    let lines = run(
+        "class Money {
+             init(cents) { this.cents = cents; }
+             __lt__(other) { return this.cents < other.cents; }
+         }
+         print Money(1) < Money(2); print Money(3) > Money(2); print Money(5) >= Money(5);",
+    );
+    assert_eq!(lines, ["true", "true", "true"]);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::{compiles, run};
+
+const CONNECT: &str = "fun connect(host, port = 8080, secure = false) { return (host, port, secure); }\n";
+
+#[test]
+fn defaults_fill_missing_arguments_and_names_pick_parameters() {
+    let source = format!(
+        "{}{}",
+        CONNECT,
+        r#"print connect("db"); print connect("db", 5432); print connect("db", secure: true);
+           print connect(port: 1, host: "h");"#
+    );
+    let lines = run(&source);
+    let expected = [r#"("db", 8080, false)"#, r#"("db", 5432, false)"#, r#"("db", 8080, true)"#, r#"("h", 1, false)"#];
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn defaults_are_evaluated_on_every_call() {
+    let source = r##"
+        var calls = 0;
+        fun next_id() { calls += 1; return calls; }
+        fun item(name, id = next_id(), label = name + "#") { return (label, id); }
+        print item("a"); print item("b"); print item("c", 10);
+        fun append(value, list = []) { push(list, value); return list; }
+        print append(1); print append(2);
+    "##;
+    assert_eq!(run(source), [r##"("a#", 1)"##, r##"("b#", 2)"##, r##"("c#", 10)"##, "[1]", "[2]"]);
+}
+
+#[test]
+fn methods_initializers_and_lambdas_take_named_arguments() {
+    let source = r#"
+        class Point {
+            init(x = 0, y = 0) { this.x = x; this.y = y; }
+            moved(dx = 0, dy = 0) { return Point(this.x + dx, this.y + dy); }
+        }
+        var p = Point(y: 2).moved(dx: 5);
+        print (p.x, p.y);
+        var greet = (name, greeting = "hello") => greeting + " " + name;
+        print greet("you", greeting: "hi");
+    "#;
+    assert_eq!(run(source), ["(5, 2)", "hi you"]);
+}
+
+#[test]
+fn bad_arguments_are_runtime_errors() {
+    let source = format!(
+        "{}{}",
+        CONNECT,
+        r#"
+        try { connect("db", timeout: 3); } catch (e) { print e.message; }
+        try { connect("db", 1, port: 2); } catch (e) { print e.message; }
+        try { connect(); } catch (e) { print e.message; }
+        try { connect(secure: true); } catch (e) { print e.message; }
+        try { connect("a", 1, true, 4); } catch (e) { print e.message; }
+        "#
+    );
+    let lines = run(&source);
+    let expected = [
+        "Unknown argument 'timeout'.",
+        "Duplicate argument 'port'.",
+        "Expected 1 to 3 arguments but got 0.",
+        "Missing argument 'host'.",
+        "Expected 1 to 3 arguments but got 4.",
+    ];
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn malformed_parameter_and_argument_lists_do_not_compile() {
+    assert!(!compiles("fun f(a = 1, b) { return a; }"));
+    assert!(!compiles("fun f(a) { return a; } f(a: 1, 2);"));
+    assert!(!compiles("fun f(a) { return a; } f(a: 1, a: 2);"));
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::run;
+
+const RECT: &str = r#"
+    class Rect {
+        width = 1;
+        height = 1;
+        tags = [];
+        static created = 0;
+
+        init(width, height) {
+            this.width = width;
+            this.height = height;
+            Rect.created += 1;
+        }
+
+        get area() { return this.width * this.height; }
+
+        get size() { return (this.width, this.height); }
+        set size(value) {
+            this.width = value[0];
+            this.height = value[1];
+        }
+
+        static square(side) { return Rect(side, side); }
+    }
+"#;
+
+fn run_with_rect(script: &str) -> Vec<String> {
+    run(&format!("{}{}", RECT, script))
+}
+
+#[test]
+fn accessors_run_on_property_syntax() {
+    let lines = run_with_rect(
+        "var r = Rect(2, 3); print r.area; r.size = (4, 5); print r.size; print r.area; print (r.size = (1, 2));",
+    );
+    assert_eq!(lines, ["6", "(4, 5)", "20", "(1, 2)"]);
+}
+
+#[test]
+fn field_initializers_are_fresh_per_instance_and_statics_are_shared() {
+    let lines = run_with_rect(
+        r#"var r = Rect(1, 1); var s = Rect.square(3); push(s.tags, "square");
+           print (s.tags, r.tags); print s.area; print Rect.created;"#,
+    );
+    assert_eq!(lines, [r#"(["square"], [])"#, "9", "2"]);
+}
+
+#[test]
+fn getter_without_setter_is_read_only() {
+    let lines = run_with_rect("var r = Rect(1, 1); try { r.area = 100; } catch (e) { print e.message; } print r.area;");
+    assert_eq!(lines, ["Property 'area' has a getter but no setter.", "1"]);
+}
+
+#[test]
+fn subclasses_inherit_accessors_fields_and_statics() {
+    let lines = run_with_rect(r#"
+        class Box < Rect {
+            depth = 2;
+            get volume() { return this.area * this.depth; }
+        }
+        var b = Box(2, 2);
+        print b.volume;
+        print Box.square(1).area;
+        print Rect.created;
+    "#);
+    assert_eq!(lines, ["8", "1", "2"]);
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{compiles, run};
+
+#[test]
+fn rest_parameters_collect_the_remaining_arguments() {
+    let source = r#"
+        fun log(level, ...args) { print (level, args); }
+        log("info");
+        log("warn", "disk", 93);
+        var tagged = (tag, ...values) => (tag, len(values));
+        print tagged("t", 1, 2);
+    "#;
+    assert_eq!(run(source), [r#"("info", [])"#, r#"("warn", ["disk", 93])"#, r#"("t", 2)"#]);
+}
+
+#[test]
+fn spread_passes_items_of_any_iterable() {
+    let source = r#"
+        fun sum(...numbers) { var total = 0; for (n in numbers) total += n; return total; }
+        var xs = [1, 2, 3];
+        print sum(...xs);
+        print sum(0, ...xs, 4, ...(5, 6));
+        print sum(...1..=10);
+        fun point(x, y, z = 0) { return (x, y, z); }
+        print point(...[1, 2]);
+        print point(..."ab");
+        fun forward(first = 0, ...rest) { return point(first, ...rest); }
+        print forward(7, 8);
+    "#;
+    assert_eq!(run(source), ["6", "21", "55", "(1, 2, 0)", r#"("a", "b", 0)"#, "(7, 8, 0)"]);
+}
+
+#[test]
+fn spread_mistakes_are_runtime_errors() {
+    let source = r#"
+        fun point(x, y, z = 0) { return (x, y, z); }
+        try { point(...[1]); } catch (e) { print e.message; }
+        try { point(...5); } catch (e) { print e.message; }
+    "#;
+    assert_eq!(run(source), ["Expected 2 to 3 arguments but got 1.", "Cannot spread int."]);
+}
+
+#[test]
+fn rest_parameter_must_come_last() {
+    assert!(!compiles("fun f(...rest, last) { return last; }"));
+}
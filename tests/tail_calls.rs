@@ -0,0 +1,62 @@
+mod common;
+
+use common::run;
+
+#[test]
+fn tail_recursion_runs_deeper_than_the_frame_limit() {
+    let source = r#"
+        fun count(n, acc) {
+            if (n == 0) return acc;
+            return count(n - 1, acc + 1);
+        }
+        print count(100000, 0);
+        fun sum(list, i = 0, acc = 0) {
+            if (i == len(list)) return acc;
+            return sum(list, i + 1, acc + list[i]);
+        }
+        print sum([1, 2, 3, 4]);
+    "#;
+    assert_eq!(run(source), ["100000", "10"]);
+}
+
+#[test]
+fn mutual_recursion_and_methods_tail_call() {
+    let source = r#"
+        fun is_even(n) { if (n == 0) return true; return is_odd(n - 1); }
+        fun is_odd(n) { if (n == 0) return false; return is_even(n - 1); }
+        print is_even(100001);
+        class Countdown {
+            run(n) { if (n == 0) return "done"; return this.run(n - 1); }
+        }
+        print Countdown().run(100000);
+    "#;
+    assert_eq!(run(source), ["false", "done"]);
+}
+
+#[test]
+fn calls_that_are_not_in_tail_position_still_overflow() {
+    let source = r#"
+        fun depth(n) { if (n == 0) return 0; return 1 + depth(n - 1); }
+        try { depth(100000); } catch (e) { print e.message; }
+        fun guarded(n) {
+            if (n == 0) return 0;
+            try { return guarded(n - 1); } finally {}
+        }
+        try { guarded(100000); } catch (e) { print e.message; }
+    "#;
+    assert_eq!(run(source), ["Stack overflow.", "Stack overflow."]);
+}
+
+#[test]
+fn closures_keep_the_values_of_reused_frames() {
+    let source = r#"
+        fun make(n, fns) {
+            if (n == 0) return fns;
+            push(fns, () => n);
+            return make(n - 1, fns);
+        }
+        var fns = make(3, []);
+        print (fns[0](), fns[1](), fns[2]());
+    "#;
+    assert_eq!(run(source), ["(3, 2, 1)"]);
+}
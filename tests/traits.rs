@@ -0,0 +1,70 @@
+mod common;
+
+use common::{compiles, run};
+
+const PRINTABLE: &str = r#"
+    trait Printable {
+        fun describe() { return "<" + this.name() + ">"; }
+    }
+    trait Serializable {
+        fun serialize() { return this.name() + ";" + str(this.version); }
+        version = 1;
+    }
+    class Plugin {
+        init(id) { this.id = id; }
+        name() { return "plugin " + this.id; }
+    }
+    class Exporter < Plugin with Printable, Serializable {
+        version = 2;
+    }
+"#;
+
+fn run_with_traits(script: &str) -> Vec<String> {
+    run(&format!("{}{}", PRINTABLE, script))
+}
+
+#[test]
+fn traits_add_methods_and_fields() {
+    let lines =
+        run_with_traits(r#"var exporter = Exporter("csv"); print exporter.describe(); print exporter.serialize();"#);
+    assert_eq!(lines, ["<plugin csv>", "plugin csv;2"]);
+}
+
+#[test]
+fn class_body_overrides_trait_members() {
+    let lines = run("trait T { fun a() { return 1; } } class C with T { a() { return 2; } } print C().a();");
+    assert_eq!(lines, ["2"]);
+}
+
+#[test]
+fn implements_follows_superclasses() {
+    let lines = run_with_traits(
+        r#"class CsvExporter < Exporter {}
+           print implements(Exporter("a"), Printable); print implements(Exporter, Serializable);
+           print implements(Plugin("raw"), Printable); print implements(CsvExporter("x"), Serializable);"#,
+    );
+    assert_eq!(lines, ["true", "true", "false", "true"]);
+}
+
+#[test]
+fn traits_are_not_classes() {
+    let lines = run_with_traits(
+        r#"try { Printable(); } catch (e) { print e.message; }
+           try { class C < Printable {} } catch (e) { print e.message; }
+           try { class D with Plugin {} } catch (e) { print e.message; }"#,
+    );
+    let expected =
+        ["Can't instantiate trait Printable.", "Superclass must be a class.", "Can only mix in traits, got Plugin."];
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn conflicting_traits_are_rejected() {
+    let clash = "trait A { fun describe() { return 1; } } trait B { fun describe() { return 2; } }";
+    assert!(!compiles(&format!("{} class Both with A, B {{}}", clash)));
+    let lines = run(&format!(
+        "{} var Hidden = B; try {{ class Both with A, Hidden {{}} }} catch (e) {{ print e.message; }}",
+        clash
+    ));
+    assert_eq!(lines, ["Method 'describe' is defined in both traits A and B."]);
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::run;
+
+#[test]
+fn tuples_print_index_and_compare() {
+    let lines = run(r#"var t = (1, "a", nil); print t; print len(t); print t[1]; print (1,); print (1, 2) == (1, 2);"#);
+    assert_eq!(lines, ["(1, \"a\", NIL)", "3", "a", "(1,)", "true"]);
+}
+
+#[test]
+fn destructuring_binds_tuples_lists_and_maps() {
+    let source = r#"
+        var (lo, hi) = (1, 9);
+        var [first, second] = ["x", "y"];
+        var {name, age: years} = {"name": "ann", "age": 31};
+        print lo; print hi; print second; print name; print years;
+        (lo, hi) = (hi, lo);
+        print lo;
+    "#;
+    assert_eq!(run(source), ["1", "9", "y", "ann", "31", "9"]);
+}
+
+#[test]
+fn divmod_floors_towards_negative_infinity() {
+    assert_eq!(run("print divmod(7, 2); print divmod(-7, 2);"), ["(3, 1)", "(-4, 1)"]);
+}
+
+#[test]
+fn mismatches_and_mutation_are_runtime_errors() {
+    let source = r#"
+        try { var (x, y) = (1, 2, 3); } catch (e) { print e.message; }
+        try { var t = (1, 2); t[0] = 3; } catch (e) { print e.message; }
+        try { divmod(7, 0); } catch (e) { print e.message; }
+    "#;
+    let lines = run(source);
+    assert_eq!(lines, ["Expected 2 values to unpack but got 3.", "Tuples are immutable.", "Integer division by zero."]);
+}